@@ -7,7 +7,13 @@ macro_rules! build {
     ( $name:ident => $code:expr ) => {
         let filename = format!("{}.ln", stringify!($name));
         write(&filename, $code)?;
-        compile(filename.to_string())?;
+        compile(
+            filename.to_string(),
+            "main".to_string(),
+            None,
+            false,
+            Vec::new(),
+        )?;
     };
 }
 
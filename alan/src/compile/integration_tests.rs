@@ -28,7 +28,7 @@ macro_rules! test {
                 let mut program = alan_compiler::program::Program::get_program();
                 program.env.insert("ALAN_TARGET".to_string(), "test".to_string());
                 alan_compiler::program::Program::return_program(program);
-                match crate::compile::build(filename.to_string()) {
+                match crate::compile::build(filename.to_string(), "main".to_string()) {
                     Ok(_) => { /* Do nothing */ }
                     Err(e) => {
                         std::fs::remove_file(&filename)?;
@@ -53,7 +53,7 @@ macro_rules! test {
                 let mut program = alan_compiler::program::Program::get_program();
                 program.env.insert("ALAN_TARGET".to_string(), "test".to_string());
                 alan_compiler::program::Program::return_program(program);
-                match crate::compile::web(filename.to_string()) {
+                match crate::compile::web(filename.to_string(), "main".to_string()) {
                     Ok(_) => { /* Do nothing */ }
                     Err(e) => {
                         std::fs::remove_file(&filename)?;
@@ -74,6 +74,10 @@ macro_rules! test {
                     Ok(a) => Ok(a),
                     Err(e) => Err(format!("Could not remove the generated JS file {:?}", e)),
                 }?;
+                match std::fs::remove_file(format!("{}.map", cmd)) {
+                    Ok(a) => Ok(a),
+                    Err(e) => Err(format!("Could not remove the generated source map {:?}", e)),
+                }?;
                 std::fs::remove_file(&filename)?;
                 Ok(())
             }
@@ -94,7 +98,7 @@ macro_rules! test {
                 let mut program = alan_compiler::program::Program::get_program();
                 program.env.insert("ALAN_TARGET".to_string(), "test".to_string());
                 alan_compiler::program::Program::return_program(program);
-                match crate::compile::build(format!("{}.ln", $entryfile)) {
+                match crate::compile::build(format!("{}.ln", $entryfile), "main".to_string()) {
                     Ok(_) => { /* Do nothing */ }
                     Err(e) => {
                         $( std::fs::remove_file($filename)?; )+
@@ -119,7 +123,7 @@ macro_rules! test {
                 let mut program = alan_compiler::program::Program::get_program();
                 program.env.insert("ALAN_TARGET".to_string(), "test".to_string());
                 alan_compiler::program::Program::return_program(program);
-                match crate::compile::web(format!("{}.ln", $entryfile)) {
+                match crate::compile::web(format!("{}.ln", $entryfile), "main".to_string()) {
                     Ok(_) => { /* Do nothing */ }
                     Err(e) => {
                         $( std::fs::remove_file($filename)?; )+
@@ -140,6 +144,10 @@ macro_rules! test {
                     Ok(a) => Ok(a),
                     Err(e) => Err(format!("Could not remove the generated JS file {:?}", e)),
                 }?;
+                match std::fs::remove_file(format!("{}.map", cmd)) {
+                    Ok(a) => Ok(a),
+                    Err(e) => Err(format!("Could not remove the generated source map {:?}", e)),
+                }?;
                 $( std::fs::remove_file($filename)?; )+
                 Ok(())
             }
@@ -163,7 +171,7 @@ macro_rules! test_gpgpu {
                 let mut program = alan_compiler::program::Program::get_program();
                 program.env.insert("ALAN_TARGET".to_string(), "test".to_string());
                 alan_compiler::program::Program::return_program(program);
-                match crate::compile::build(filename.to_string()) {
+                match crate::compile::build(filename.to_string(), "main".to_string()) {
                     Ok(_) => { /* Do nothing */ }
                     Err(e) => {
                         std::fs::remove_file(&filename)?;
@@ -195,7 +203,7 @@ macro_rules! test_gpgpu {
                     let mut program = alan_compiler::program::Program::get_program();
                     program.env.insert("ALAN_TARGET".to_string(), "test".to_string());
                     alan_compiler::program::Program::return_program(program);
-                    match crate::compile::web(filename.to_string()) {
+                    match crate::compile::web(filename.to_string(), "main".to_string()) {
                         Ok(_) => { /* Do nothing */ }
                         Err(e) => {
                             std::fs::remove_file(&filename)?;
@@ -248,6 +256,10 @@ macro_rules! test_gpgpu {
                         Ok(a) => Ok(a),
                         Err(e) => Err(format!("Could not remove the generated JS file {:?}", e)),
                     }?;
+                    match std::fs::remove_file(format!("{}.map", jsfile)) {
+                        Ok(a) => Ok(a),
+                        Err(e) => Err(format!("Could not remove the generated source map {:?}", e)),
+                    }?;
                     match std::fs::remove_file(&htmlfile) {
                         Ok(a) => Ok(a),
                         Err(e) => Err(format!("Could not remove the generated HTML file {:?}", e)),
@@ -277,7 +289,7 @@ macro_rules! test_ignore {
                 let mut program = alan_compiler::program::Program::get_program();
                 program.env.insert("ALAN_TARGET".to_string(), "test".to_string());
                 alan_compiler::program::Program::return_program(program);
-                match crate::compile::build(filename.to_string()) {
+                match crate::compile::build(filename.to_string(), "main".to_string()) {
                     Ok(_) => { /* Do nothing */ }
                     Err(e) => {
                         std::fs::remove_file(&filename)?;
@@ -306,7 +318,13 @@ macro_rules! test_compile_error {
             fn $rule() -> Result<(), Box<dyn std::error::Error>> {
                 let filename = format!("{}.ln", stringify!($rule));
                 std::fs::write(&filename, $code)?;
-                let res = crate::compile::compile(filename.to_string());
+                let res = crate::compile::compile(
+                    filename.to_string(),
+                    "main".to_string(),
+                    None,
+                    false,
+                    Vec::new(),
+                );
                 std::fs::remove_file(&filename)?;
                 match res {
                     Ok(_) => Err("Unexpectedly succeeded!".into()),
@@ -322,7 +340,13 @@ macro_rules! test_compile_error {
             fn $rule() -> Result<(), Box<dyn std::error::Error>> {
                 let filename = format!("{}.ln", stringify!($rule));
                 std::fs::write(&filename, $code)?;
-                let res = crate::compile::compile(filename.to_string());
+                let res = crate::compile::compile(
+                    filename.to_string(),
+                    "main".to_string(),
+                    None,
+                    false,
+                    Vec::new(),
+                );
                 std::fs::remove_file(&filename)?;
                 match res {
                     Ok(_) => Err("Unexpectedly succeeded!".into()),
@@ -370,6 +394,21 @@ macro_rules! stdout_js {
     };
 }
 #[cfg(test)]
+macro_rules! stdout_one_of {
+    ( $test_val:expr, $in_rs:expr, $real_val:expr ) => {
+        let std_out = if cfg!(windows) {
+            String::from_utf8($real_val.stdout.clone())?.replace("\r\n", "\n")
+        } else {
+            String::from_utf8($real_val.stdout.clone())?
+        };
+        assert!(
+            $test_val.contains(&std_out.as_str()),
+            "Unexpected stdout {:?}",
+            std_out
+        );
+    };
+}
+#[cfg(test)]
 macro_rules! stdout_contains {
     ( $test_val:expr, $in_rs:expr, $real_val:expr ) => {
         let std_out = if cfg!(windows) {
@@ -391,6 +430,20 @@ macro_rules! stderr {
         assert_eq!($test_val, &std_err);
     };
 }
+/// Checks the frame saved to the named file has the expected dimensions and that every pixel is
+/// the expected color, then deletes it
+#[cfg(test)]
+macro_rules! frame_rs {
+    ( $test_val:expr, $in_rs:expr, $real_val:expr ) => {
+        if $in_rs {
+            let (path, dimensions, pixel) = $test_val;
+            let frame = image::open(path)?.to_rgba8();
+            std::fs::remove_file(path)?;
+            assert_eq!(frame.dimensions(), dimensions);
+            assert!(frame.pixels().all(|p| p.0 == pixel));
+        }
+    };
+}
 #[cfg(test)]
 macro_rules! status {
     ( $test_val:expr, $in_rs:expr, $real_val:expr ) => {
@@ -494,6 +547,46 @@ test!(stdout_and_stderr => r#"
     stdout "Hello, World!\n";
     stderr "Goodbye, World!\n";
 );
+test!(string_interpolation => r#"
+    export fn main {
+      const x = 5;
+      const name = 'Alan';
+      print(f"x is {x}");
+      print(f'{name} says {x} + {x}');
+      print(f'{{"foo": {x}}}');
+      print(f'{{x}} is {x}');
+      print(f'`{name}` costs ${x}');
+      print([1, 2].map(fn (x: i64) = f'{name} has {x}').join(', '));
+    }"#;
+    stdout "x is 5\nAlan says 5 + 5\n{\"foo\": 5}\n{x} is 5\n`Alan` costs $5\nAlan has 1, Alan has 2\n";
+);
+test!(string_no_interpolation => r#"
+    export fn main {
+      const x = 5;
+      print('{x} is not interpolated');
+      const shader = "
+        @compute
+        @workgroup_size(1)
+        fn main(@builtin(global_invocation_id) id: vec3u) {
+          let x = id.x;
+        }";
+      print(shader);
+    }"#;
+    stdout "{x} is not interpolated\n\n        @compute\n        @workgroup_size(1)\n        fn main(@builtin(global_invocation_id) id: vec3u) {\n          let x = id.x;\n        }\n";
+);
+test_compile_error!(string_interpolation_unknown_variable => r#"
+    export fn main {
+      print(f'{y} is not defined');
+    }"#;
+    error "Couldn't find variable y to interpolate in format string \"{y} is not defined\"";
+);
+test_compile_error!(string_interpolation_lone_brace => r#"
+    export fn main {
+      const x = 5;
+      print(f'{x} }');
+    }"#;
+    error "Unmatched `}` in format string \"{x} }\". Use `}}` for a literal `}`";
+);
 
 // TODO: Unify the string output for these two so it can be tested more reliably
 test!(string_parse => r#"
@@ -556,6 +649,75 @@ test_gpgpu!(list_of_gpu_tasks => r#"
     }"#;
     stdout "[0, 2, 4, 6, 8, 10, 12, 14]\n[0, 3, 6, 9]\n";
 );
+test_gpgpu!(gpu_wait_idle => r#"
+    export fn main {
+      let b = GBuffer(filled(3.i32, 4));
+      let idx = gFor(4);
+      let compute = b[idx].store(b[idx] + idx.gi32);
+      compute.build.run;
+      gpuWaitIdle();
+      b.read{i32}.print;
+    }"#;
+    stdout "[3, 4, 5, 6]\n";
+);
+
+test_gpgpu!(gpu_indirect_dispatch => r#"
+    export fn main {
+      let count = GBuffer{u32}(indirectBuffer(), [0.u32, 0.u32, 0.u32]);
+      let b = GBuffer(filled(0.i32, 8));
+      let sizer = GPGPU("
+        @group(0)
+        @binding(0)
+        var<storage, read_write> count: array<u32>;
+
+        @compute
+        @workgroup_size(1)
+        fn main() {
+          count[0] = 4u;
+          count[1] = 1u;
+          count[2] = 1u;
+        }
+      ", [[count]], {i64[3]}(1, 1, 1));
+      let worker = GPGPU("
+        @group(0)
+        @binding(0)
+        var<storage, read_write> vals: array<i32>;
+
+        @compute
+        @workgroup_size(1)
+        fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+          vals[id.x] = i32(id.x) + 1;
+        }
+      ", [[b]], count).getOrExit;
+      [sizer, worker].run;
+      b.read{i32}.print;
+    }"#;
+    stdout "[1, 2, 3, 4, 0, 0, 0, 0]\n";
+);
+
+test_gpgpu!(gpu_set_buffers => r#"
+    export fn main {
+      let b1 = GBuffer(filled(2.i32, 4));
+      let b2 = GBuffer(filled(3.i32, 4));
+      let plan = GPGPU("
+        @group(0)
+        @binding(0)
+        var<storage, read_write> vals: array<i32>;
+
+        @compute
+        @workgroup_size(1)
+        fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+          vals[id.x] = vals[id.x] * i32(id.x);
+        }
+      ", b1);
+      plan.run;
+      plan.setBuffers([[b2]]);
+      plan.run;
+      b1.read{i32}.print;
+      b2.read{i32}.print;
+    }"#;
+    stdout "[0, 2, 4, 6]\n[0, 3, 6, 9]\n";
+);
 
 test_gpgpu!(hello_gpu_odd => r#"
     export fn main {
@@ -599,6 +761,56 @@ test_gpgpu!(gpu_replace => r#"
     stdout "[3, 4, 5, 6]\n[1, 2, 3, 4]\n";
 );
 
+test_gpgpu!(gpu_copy => r#"
+    export fn main {
+        let src = GBuffer([1.i32, 2.i32, 3.i32, 4.i32]);
+        let dst = GBuffer{i32}(4);
+        src.copy(dst);
+        dst.read{i32}.print;
+        src.copy(GBuffer{i32}(3)).failed.print;
+    }"#;
+    stdout "[1, 2, 3, 4]\ntrue\n";
+);
+
+test_gpgpu!(gpu_f16_round_trip => r#"
+    export fn main {
+        // GPUs without the `SHADER_F16` feature can't create `f16` buffers, and say so instead
+        const b = GBufferF16([1.0.f32, 0.5.f32, -2.25.f32, 0.1.f32]);
+        if(b.failed, fn = print(b.Error!!), fn {
+            const vals = b.getOrExit.readF16;
+            vals.take(3).map(fn (v: f32) = v.string).join(', ').print;
+            ((vals[3]!! - 0.1.f32).abs < 0.001.f32).print;
+            // The padding after an odd number of values isn't part of the buffer
+            const odd = GBufferF16([1.0.f32, 2.0.f32, 3.0.f32]).getOrExit;
+            odd.cpulen.print;
+            odd.readF16.len.print;
+        });
+    }"#;
+    stdout_one_of [
+        "1, 0.5, -2.25\ntrue\n3\n3\n",
+        "Error: This GPU does not support f16 shaders\n",
+    ];
+);
+
+test_gpgpu!(gpu_bytes_round_trip => r#"
+    export fn main {
+        const b = GBufferBytes([0.u8, 1.u8, 127.u8, 128.u8, 200.u8, 255.u8, 7.u8, 42.u8]);
+        b.cpulen.print;
+        b.readBytes.print;
+    }"#;
+    stdout "8\n[0, 1, 127, 128, 200, 255, 7, 42]\n";
+);
+
+test_gpgpu!(gpu_bytes_round_trip_padded => r#"
+    export fn main {
+        // Buffers are padded to a multiple of four bytes, which isn't read back
+        const b = GBufferBytes([9.u8, 8.u8, 7.u8, 6.u8, 5.u8]);
+        b.cpulen.print;
+        b.readBytes.print;
+    }"#;
+    stdout "5\n[9, 8, 7, 6, 5]\n";
+);
+
 test_gpgpu!(gpu_abs => r#"
     export fn main {
         let b = GBuffer([1.i32, -2.i32, -3.i32, 4.i32]);
@@ -1355,6 +1567,40 @@ test!(file_reading "file_reading" =>
     stdout "Hello, World!\n";
 );
 
+test!(file_writing "file_writing" =>
+    // Listed so the test cleans it up afterwards, and the stale contents show that `write` truncates
+    "file_writing_output.txt" => "Stale contents",
+    "file_writing.ln" => r#"
+        type File <-- '@std/fs';
+        fn string <-- '@std/fs';
+        fn bytes <-- '@std/fs';
+        fn write <-- '@std/fs';
+        fn append <-- '@std/fs';
+
+        export fn main {
+            const f = File('./file_writing_output.txt');
+            f.write('Hello');
+            f.append(', World!');
+            f.string.getOr('').print;
+            f.bytes.getOr(Array{u8}()).len.print;
+            File('./file_writing_missing.txt').string.getOr('missing').print;
+        }
+    "#;
+    stdout "Hello, World!\n13\nmissing\n";
+);
+
+test!(exec_subprocess => r#"
+    export fn main {
+      // Node is always available where these tests run, making it a portable `echo`
+      const res = exec('node', ['-e', 'process.stdout.write("hi"); process.stderr.write("bye"); process.exit(3)']).getOrExit;
+      res.stdout.print;
+      res.stderr.print;
+      res.code.print;
+      exec('alan_command_that_does_not_exist', Array{string}()).failed.print;
+    }"#;
+    stdout "hi\nbye\n3\ntrue\n";
+);
+
 // Maybe, Result, and Either
 
 test!(maybe => r#"
@@ -1413,6 +1659,67 @@ Error: Divide by zero error!
 Error: there is no error
 "#;
 );
+test!(parmap_result => r#"
+    fn half(val: i64) = if(val % 2 == 0, fn {
+      return Fallible{i64}(val / 2);
+    }, fn {
+      return Error{i64}('odd value');
+    });
+    fn errorOr{T}(v: T!) = v.Error.getOr(Error('there is no error'));
+
+    export fn main {
+      const halves = [2, 4, 6].parmapResult(half);
+      print(halves.failed);
+      print(halves.getOr([0]).map(string).join(', '));
+
+      const odd = [2, 3, 5].parmapResult(half);
+      print(odd.errorOr);
+    }"#;
+    stdout r#"false
+1, 2, 3
+Error: Element 1 failed: odd value
+"#;
+);
+test!(try_all_and_partition_results => r#"
+    fn half(val: i64) = if(val % 2 == 0, fn {
+      return Fallible{i64}(val / 2);
+    }, fn {
+      return Error{i64}('odd value '.concat(val.string));
+    });
+    fn errorOr{T}(v: T!) = v.Error.getOr(Error('there is no error'));
+
+    export fn main {
+      const halves = [2, 4, 6].map(half).tryAll;
+      print(halves.failed);
+      print(halves.getOr([0]).map(string).join(', '));
+
+      const odd = [2, 3, 5].map(half).tryAll;
+      print(odd.errorOr);
+
+      const parts = [2, 3, 4, 5].map(half).partitionResults;
+      print(parts.0.map(string).join(', '));
+      print(parts.1.len);
+    }"#;
+    stdout r#"false
+1, 2, 3
+Error: odd value 3
+1, 2
+2
+"#;
+);
+test!(flatten_maybe_array => r#"
+    fn half(val: i64) = if(val % 2 == 0, fn = val / 2);
+
+    export fn main {
+      print([1, 2, 3, 4, 6].map(half).flatten.map(string).join(', '));
+      print([1, 3, 5].map(half).flatten.len);
+      print([2, 4, 6].map(half).flatten.map(string).join(', '));
+    }"#;
+    stdout r#"1, 2, 3
+0
+1, 2, 3
+"#;
+);
 
 // Types
 
@@ -1522,6 +1829,51 @@ test_compile_error!(undefined_function_call => r#"
     }"#;
     error "Could not find a function with a call signature of i64str(i64)";
 );
+test_compile_error!(duplicate_function_definition => r#"
+    fn double(x: i64) -> i64 = x * 2;
+    fn double(x: i64) -> i64 = x + x;
+
+    export fn main {
+      print(double(5));
+    }"#;
+    error "Duplicate definition of double(i64) at duplicate_function_definition.ln:2 and duplicate_function_definition.ln:3";
+);
+test_compile_error!(main_not_exported => r#"
+    fn main {
+      print('Hello, World!');
+    }"#;
+    error "Entry file `main_not_exported.ln` has a `main` function but it is not exported. Add `export` to it: `export fn main`";
+);
+test_compile_error!(main_missing => r#"
+    export fn mian {
+      print('Hello, World!');
+    }"#;
+    error "Entry file `main_missing.ln` has no function named `main`. It must define and export one. Exported names found: `mian`";
+);
+test_compile_error!(main_missing_lists_exports => r#"
+    export fn run {
+      print('Hello, World!');
+    }
+
+    fn helper = 'unexported';"#;
+    error "Entry file `main_missing_lists_exports.ln` has no function named `main`. It must define and export one. Exported names found: `run`";
+);
+test_compile_error!(main_takes_arguments => r#"
+    export fn main(name: string) {
+      print(name);
+    }"#;
+    error "Entry function `main` in `main_takes_arguments.ln` takes 1 argument(s), but the entry function must take none";
+);
+test_compile_error!(main_overloaded => r#"
+    export fn main {
+      print('Hello, World!');
+    }
+
+    export fn main(name: string) {
+      print(name);
+    }"#;
+    error "Entry function `main` in `main_overloaded.ln` has 2 definitions, but the entry function can't be overloaded";
+);
 test_ignore!(totally_broken_statement => r#"
     on app.start {
       app.oops
@@ -1529,6 +1881,1067 @@ test_ignore!(totally_broken_statement => r#"
     stderr "what";
 );
 
+// Compiler Warnings
+
+#[cfg(test)]
+mod unused_import_warning {
+    #[test]
+    fn unused_import_warning() -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(
+            "unused_import_warning_dep.ln",
+            r#"
+    export fn used = "Used";
+    export fn unused = "Unused";"#,
+        )?;
+        std::fs::write(
+            "unused_import_warning.ln",
+            r#"
+    fn used <-- './unused_import_warning_dep.ln';
+    fn unused <-- './unused_import_warning_dep.ln';
+
+    export fn main {
+      used().print;
+    }"#,
+        )?;
+        let res = crate::compile::check("unused_import_warning.ln".to_string());
+        std::fs::remove_file("unused_import_warning_dep.ln")?;
+        std::fs::remove_file("unused_import_warning.ln")?;
+        let warnings = res?;
+        assert_eq!(
+            warnings,
+            vec!["unused_import_warning.ln:3: unused import `unused`".to_string()]
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod print_ast {
+    #[test]
+    fn print_ast() -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(
+            "print_ast.ln",
+            r#"
+    const greeting = 'Hello, World!';
+
+    export fn main {
+      print(greeting);
+    }"#,
+        )?;
+        let res = crate::compile::ast("print_ast.ln".to_string());
+        std::fs::remove_file("print_ast.ln")?;
+        let out = res?;
+        assert!(out.starts_with("scope print_ast.ln\n"));
+        assert!(out.contains("\n  const greeting\n"));
+        assert!(out.contains("\n  export fn main :: "));
+        // The body is nested under the function
+        assert!(out.contains("\n    call print :: "));
+        // The standard library isn't included
+        assert!(!out.contains("scope @root"));
+        Ok(())
+    }
+}
+
+// Entry Points
+
+#[cfg(test)]
+mod entry_start {
+    #[test]
+    fn entry_start() -> Result<(), Box<dyn std::error::Error>> {
+        alan_compiler::program::Program::set_target_lang_rs();
+        std::fs::write(
+            "entry_start.ln",
+            r#"
+    export fn start {
+      print('Started!');
+    }"#,
+        )?;
+        let res = crate::compile::compile(
+            "entry_start.ln".to_string(),
+            "start".to_string(),
+            None,
+            false,
+            Vec::new(),
+        );
+        std::fs::remove_file("entry_start.ln")?;
+        res?;
+        let cmd = if cfg!(windows) {
+            ".\\entry_start.exe"
+        } else {
+            "./entry_start"
+        };
+        let run = std::process::Command::new(cmd).output()?;
+        std::fs::remove_file(cmd)?;
+        assert_eq!(String::from_utf8(run.stdout)?.trim_end(), "Started!");
+        Ok(())
+    }
+}
+test!(test_functions => r#"
+    export fn test_addition = 1 + 1 == 2;
+    export fn test_subtraction = 3 - 1 == 1;
+    fn test_not_exported = false;"#;
+    stdout "✅ test_addition\n❎ test_subtraction\n1 passed, 1 failed\n";
+    stderr "Failed tests: test_subtraction\n";
+    status 1;
+);
+
+// Source maps
+
+/// `to_js` also writes out the npm dependencies it needs to a `package.json` in the current
+/// directory, which every test shares. The guard makes the tests calling it take turns, and puts
+/// back whatever `package.json` was there before once it is dropped
+#[cfg(test)]
+struct PackageJsonGuard {
+    prior: Option<String>,
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+static PACKAGE_JSON_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+#[cfg(test)]
+impl PackageJsonGuard {
+    fn new() -> PackageJsonGuard {
+        // A test that panicked while holding the lock still restored the file when unwinding
+        let lock = PACKAGE_JSON_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        PackageJsonGuard {
+            prior: std::fs::read_to_string("package.json").ok(),
+            _lock: lock,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Drop for PackageJsonGuard {
+    fn drop(&mut self) {
+        let _ = match &self.prior {
+            Some(p) => std::fs::write("package.json", p),
+            None if std::path::Path::new("package.json").exists() => {
+                std::fs::remove_file("package.json")
+            }
+            None => Ok(()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod js_source_map {
+    #[test]
+    fn js_source_map() -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(
+            "js_source_map.ln",
+            r#"
+    export fn main {
+      print('Hello, World!');
+    }"#,
+        )?;
+        let _package_json = super::PackageJsonGuard::new();
+        let res = crate::compile::to_js(
+            "js_source_map.ln".to_string(),
+            "main".to_string(),
+            crate::compile::JsModule::Esm,
+        );
+        std::fs::remove_file("js_source_map.ln")?;
+        res?;
+        let js = std::fs::read_to_string("js_source_map.js")?;
+        let map = std::fs::read_to_string("js_source_map.js.map")?;
+        std::fs::remove_file("js_source_map.js")?;
+        std::fs::remove_file("js_source_map.js.map")?;
+        assert!(js.contains("//# sourceMappingURL=js_source_map.js.map"));
+        assert!(map.contains("\"sources\":[\"js_source_map.ln\"]"));
+        Ok(())
+    }
+}
+
+// Module output
+
+#[cfg(test)]
+mod to_js_commonjs {
+    #[test]
+    fn to_js_commonjs() -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(
+            "to_js_commonjs.ln",
+            r#"
+    export fn main {
+      print(5);
+    }"#,
+        )?;
+        let _package_json = super::PackageJsonGuard::new();
+        let res = crate::compile::to_js(
+            "to_js_commonjs.ln".to_string(),
+            "main".to_string(),
+            crate::compile::JsModule::Cjs,
+        );
+        std::fs::remove_file("to_js_commonjs.ln")?;
+        res?;
+        let js = std::fs::read_to_string("to_js_commonjs.js")?;
+        std::fs::remove_file("to_js_commonjs.js")?;
+        std::fs::remove_file("to_js_commonjs.js.map")?;
+        assert!(js.contains("const alan_std = require(\"alan_std\");"));
+        assert!(!js.contains("import * as"));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod to_rs_module {
+    #[test]
+    fn to_rs_module() -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(
+            "to_rs_module.ln",
+            r#"
+    export fn start {
+      print(5);
+    }"#,
+        )?;
+        let res = crate::compile::to_rs(
+            "to_rs_module.ln".to_string(),
+            "start".to_string(),
+            crate::compile::RsEmit::Module,
+            Vec::new(),
+        );
+        std::fs::remove_file("to_rs_module.ln")?;
+        res?;
+        let rs = std::fs::read_to_string("to_rs_module.rs")?;
+        std::fs::remove_file("to_rs_module.rs")?;
+        assert!(rs.contains("pub fn start()"));
+        assert!(!rs.contains("fn main"));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod to_rs_staticlib {
+    #[test]
+    fn to_rs_staticlib() -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(
+            "to_rs_staticlib.ln",
+            r#"
+    export fn double(x: i64) -> i64 = x * 2;"#,
+        )?;
+        let res = crate::compile::to_rs(
+            "to_rs_staticlib.ln".to_string(),
+            "main".to_string(),
+            crate::compile::RsEmit::Staticlib,
+            Vec::new(),
+        );
+        std::fs::remove_file("to_rs_staticlib.ln")?;
+        res?;
+        let rs = std::fs::read_to_string("to_rs_staticlib.rs")?;
+        std::fs::remove_file("to_rs_staticlib.rs")?;
+        let lib = if cfg!(windows) {
+            "to_rs_staticlib.lib"
+        } else {
+            "libto_rs_staticlib.a"
+        };
+        let built = std::path::Path::new(lib).exists();
+        if built {
+            std::fs::remove_file(lib)?;
+        }
+        assert!(rs.contains("#[no_mangle]\npub extern \"C\" fn double(x: i64) -> i64"));
+        assert!(!rs.contains("fn main"));
+        assert!(built);
+        Ok(())
+    }
+
+    #[test]
+    fn to_rs_staticlib_not_ffi_safe() -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(
+            "to_rs_staticlib_not_ffi_safe.ln",
+            r#"
+    export fn greet(name: string) -> string = 'Hello, '.concat(name);"#,
+        )?;
+        let res = crate::compile::to_rs(
+            "to_rs_staticlib_not_ffi_safe.ln".to_string(),
+            "main".to_string(),
+            crate::compile::RsEmit::Staticlib,
+            Vec::new(),
+        );
+        std::fs::remove_file("to_rs_staticlib_not_ffi_safe.ln")?;
+        match res {
+            Ok(_) => Err("Unexpectedly succeeded!".into()),
+            Err(e) => {
+                assert_eq!(
+                    format!("{}", e),
+                    "Exported function `greet` can't be included in a static library because its argument `name` has type `string`. Only numbers and booleans can be passed to and from C"
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+// Watch mode
+
+#[cfg(test)]
+mod watch_recompile {
+    #[test]
+    fn watch_recompile() -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write("watch_recompile.ln", "export fn main = print('Hello');")?;
+        let path = std::fs::canonicalize("watch_recompile.ln")?;
+        let (tx, rx) = std::sync::mpsc::channel();
+        // Simulate an editor saving the file in two quick writes, plus an unrelated change
+        std::fs::write("watch_recompile.ln", "export fn main = print('Goodbye');")?;
+        for p in [
+            path.clone(),
+            path.clone(),
+            path.with_file_name("unrelated.ln"),
+        ] {
+            tx.send(Ok(notify::Event::new(notify::EventKind::Modify(
+                notify::event::ModifyKind::Any,
+            ))
+            .add_path(p)))?;
+        }
+        drop(tx);
+        let mut builds = 0;
+        let res = crate::compile::watch_loop(&rx, || {
+            builds += 1;
+            std::collections::HashSet::from([path.clone()])
+        });
+        std::fs::remove_file("watch_recompile.ln")?;
+        res?;
+        // The initial build plus a single, debounced, rebuild
+        assert_eq!(builds, 2);
+        Ok(())
+    }
+}
+
+// Dependency graph
+
+#[cfg(test)]
+mod imported_files {
+    #[test]
+    fn imported_files() -> Result<(), Box<dyn std::error::Error>> {
+        use alan_compiler::program::Program;
+        std::fs::write(
+            "imported_files_c.ln",
+            "export fn greeting = 'Hello, World!';",
+        )?;
+        std::fs::write(
+            "imported_files_b.ln",
+            r#"
+    fn greeting <-- './imported_files_c.ln';
+    export fn shout = greeting().concat('!!');"#,
+        )?;
+        std::fs::write(
+            "imported_files.ln",
+            r#"
+    fn shout <-- './imported_files_b.ln';
+    export fn main = shout().print;"#,
+        )?;
+        Program::set_target_lang_rs();
+        let loaded = Program::load("./imported_files.ln".to_string());
+        std::fs::remove_file("imported_files.ln")?;
+        std::fs::remove_file("imported_files_b.ln")?;
+        std::fs::remove_file("imported_files_c.ln")?;
+        loaded?;
+        let program = Program::get_program();
+        let files = program.imported_files("./imported_files.ln");
+        Program::return_program(program);
+        assert_eq!(
+            files?,
+            vec![
+                "./imported_files.ln".to_string(),
+                "./imported_files_b.ln".to_string(),
+                "./imported_files_c.ln".to_string(),
+            ]
+        );
+        Ok(())
+    }
+}
+
+// Multiple targets
+
+#[cfg(test)]
+mod compile_all_targets {
+    #[test]
+    fn compile_all_targets() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::compile::Target;
+        std::fs::write(
+            "compile_all_targets.ln",
+            "export fn main = print('Hello, World!');",
+        )?;
+        let parses = alan_compiler::program::Program::parse_count();
+        let res = crate::compile::compile_all(
+            "compile_all_targets.ln".to_string(),
+            vec![Target::Rs, Target::Js],
+        );
+        std::fs::remove_file("compile_all_targets.ln")?;
+        let out = res?;
+        assert_eq!(out.len(), 2);
+        assert!(out[0].contains("fn main()"));
+        assert!(out[1].contains("async function main()"));
+        assert_eq!(alan_compiler::program::Program::parse_count() - parses, 1);
+        Ok(())
+    }
+}
+
+// In-memory compilation
+
+#[cfg(test)]
+mod compile_str_hello_world {
+    #[test]
+    fn compile_str_hello_world() -> Result<(), Box<dyn std::error::Error>> {
+        let js = crate::compile::compile_str(
+            "compile_str_hello_world.ln",
+            "export fn main = print('Hello, World!');",
+            crate::compile::Target::Js,
+        )?;
+        assert!(js.contains("console.log"));
+        assert!(js.trim_end().ends_with("main();"));
+        assert!(!std::path::Path::new("compile_str_hello_world.ln").exists());
+        Ok(())
+    }
+}
+
+// Dependency listing
+
+#[cfg(test)]
+mod deps_gpu {
+    #[test]
+    fn deps_gpu() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::compile::Target;
+        std::fs::write(
+            "deps_gpu.ln",
+            "export fn main = GBuffer(filled(2.i32, 4)).read{i32}.print;",
+        )?;
+        let rs = crate::compile::deps("deps_gpu.ln".to_string(), "main".to_string(), Target::Rs);
+        let js = crate::compile::deps("deps_gpu.ln".to_string(), "main".to_string(), Target::Js);
+        std::fs::remove_file("deps_gpu.ln")?;
+        let rs = rs?;
+        let js = js?;
+        assert!(rs.starts_with("[dependencies]\n"));
+        assert!(rs.contains("alan_std = { git = \"https://github.com/alantech/alan.git\" }"));
+        assert!(js.contains("\"alan_std\": \"https://github.com/alantech/alan.git\""));
+        Ok(())
+    }
+}
+
+// Generated Cargo.toml
+
+#[cfg(test)]
+mod cargo_deps_sorted {
+    #[test]
+    fn cargo_deps_sorted() -> Result<(), Box<dyn std::error::Error>> {
+        let deps = [
+            ("zeta", "https://example.com/zeta.git"),
+            ("alan_std", "https://github.com/alantech/alan.git"),
+            ("mid", "https://example.com/mid.git#dev"),
+            ("zeta", "https://example.com/zeta.git"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<Vec<(String, String)>>();
+        let out = crate::compile::cargo_deps(deps.iter().map(|(k, v)| (k, v)), &[])?;
+        assert_eq!(
+            out,
+            r#"alan_std = { git = "https://github.com/alantech/alan.git" }
+mid = { git = "https://example.com/mid.git", branch = "dev" }
+zeta = { git = "https://example.com/zeta.git" }"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cargo_deps_conflict() {
+        let deps = [
+            (
+                "zeta".to_string(),
+                "https://example.com/zeta.git".to_string(),
+            ),
+            (
+                "zeta".to_string(),
+                "https://example.com/zeta.git#dev".to_string(),
+            ),
+        ];
+        assert!(crate::compile::cargo_deps(deps.iter().map(|(k, v)| (k, v)), &[]).is_err());
+    }
+
+    #[test]
+    fn cargo_deps_features() -> Result<(), Box<dyn std::error::Error>> {
+        let deps = [
+            ("zeta", "https://example.com/zeta.git"),
+            ("mid", "https://example.com/mid.git#dev"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<Vec<(String, String)>>();
+        let features = ["zeta/serde", "mid/fast", "zeta/async", "zeta/serde"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect::<Vec<String>>();
+        let out = crate::compile::cargo_deps(deps.iter().map(|(k, v)| (k, v)), &features)?;
+        let cargo_str = format!("[dependencies]\n{}", out);
+        assert_eq!(
+            cargo_str,
+            r#"[dependencies]
+mid = { git = "https://example.com/mid.git", branch = "dev", features = ["fast"] }
+zeta = { git = "https://example.com/zeta.git", features = ["async", "serde"] }"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cargo_deps_bad_features() {
+        let deps = [(
+            "zeta".to_string(),
+            "https://example.com/zeta.git".to_string(),
+        )];
+        for feature in ["other/serde", "zeta", "zeta/", "/serde"] {
+            assert!(crate::compile::cargo_deps(
+                deps.iter().map(|(k, v)| (k, v)),
+                &[feature.to_string()]
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn cargo_deps_conflicting_imports() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::compile::Target;
+        std::fs::write(
+            "cargo_deps_conflicting_imports.ln",
+            r#"
+            type{Rs} OtherBacking = Rust{"alan_std" @ "https://github.com/alantech/alan.git#dev"};
+            fn{Rs} otherNowMillis "alan_std::now_millis" <- OtherBacking :: () -> i64;
+
+            export fn main {
+              nowMillis().print;
+              otherNowMillis().print;
+            }"#,
+        )?;
+        let res = crate::compile::deps(
+            "cargo_deps_conflicting_imports.ln".to_string(),
+            "main".to_string(),
+            Target::Rs,
+        );
+        std::fs::remove_file("cargo_deps_conflicting_imports.ln")?;
+        match res {
+            Ok(_) => Err("Unexpectedly succeeded!".into()),
+            Err(e) => {
+                assert!(
+                    format!("{}", e).starts_with("Dependency alan_std has conflicting versions")
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+// Build cache location
+
+#[cfg(test)]
+mod cache_dir_override {
+    #[test]
+    fn cache_dir_override() {
+        let dir = std::env::temp_dir().join("alan_cache_dir_override");
+        assert_eq!(crate::compile::cache_dir(Some(dir.clone().into())), dir);
+        // An empty override is treated as if it were not set
+        let default = crate::compile::cache_dir(None);
+        assert_eq!(crate::compile::cache_dir(Some("".into())), default);
+        assert!(default.ends_with("alan"));
+    }
+}
+
+#[cfg(test)]
+mod no_cache_build {
+    use std::path::{Path, PathBuf};
+    use std::time::SystemTime;
+
+    /// Lists every file within the directory, with when it was last modified and how big it is
+    fn snapshot(dir: &Path, out: &mut Vec<(PathBuf, SystemTime, u64)>) -> std::io::Result<()> {
+        if !dir.exists() {
+            return Ok(());
+        }
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if meta.is_dir() {
+                snapshot(&entry.path(), out)?;
+            } else {
+                out.push((entry.path(), meta.modified()?, meta.len()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the build with the shared build cache locked, so the other tests can't change it in the
+    /// meantime and a build that tried to use it would time out waiting for the lock, then asserts
+    /// that the shared cache is unchanged and the fresh build directory was cleaned up
+    fn assert_cache_untouched(
+        build: impl FnOnce() -> Result<(), Box<dyn std::error::Error>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let shared = crate::compile::cache_dir(std::env::var_os("ALAN_CACHE_DIR"));
+        assert!(!crate::compile::uncached_dir()?.starts_with(&shared));
+        let lockfile_path = shared.join(".lockfile");
+        let lockfile = if lockfile_path.exists() {
+            let lockfile = std::fs::File::open(&lockfile_path)?;
+            crate::compile::acquire_file_lock(
+                &lockfile,
+                &lockfile_path,
+                std::time::Duration::from_secs(600),
+            )?;
+            Some(lockfile)
+        } else {
+            None
+        };
+        let mut before = Vec::new();
+        snapshot(&shared, &mut before)?;
+        let res = build();
+        let mut after = Vec::new();
+        let snapshot_res = snapshot(&shared, &mut after);
+        if let Some(lockfile) = lockfile {
+            fs2::FileExt::unlock(&lockfile)?;
+        }
+        res?;
+        snapshot_res?;
+        before.sort();
+        after.sort();
+        assert!(before == after, "The shared build cache was modified");
+        let prefix = format!("alan_uncached_{}_", std::process::id());
+        let leftovers = std::fs::read_dir(std::env::temp_dir())?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+            .count();
+        assert_eq!(leftovers, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn no_cache_build() -> Result<(), Box<dyn std::error::Error>> {
+        alan_compiler::program::Program::set_target_lang_rs();
+        std::fs::write(
+            "no_cache_build.ln",
+            "export fn main = print('Hello, World!');",
+        )?;
+        let res = assert_cache_untouched(|| {
+            crate::compile::compile(
+                "no_cache_build.ln".to_string(),
+                "main".to_string(),
+                None,
+                true,
+                Vec::new(),
+            )
+        });
+        std::fs::remove_file("no_cache_build.ln")?;
+        res?;
+        let cmd = if cfg!(windows) {
+            ".\\no_cache_build.exe"
+        } else {
+            "./no_cache_build"
+        };
+        let run = std::process::Command::new(cmd).output()?;
+        std::fs::remove_file(cmd)?;
+        assert_eq!(String::from_utf8(run.stdout)?, "Hello, World!\n");
+        Ok(())
+    }
+
+    #[test]
+    fn no_cache_bundle() -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(
+            "no_cache_bundle.ln",
+            "export fn main = print('Hello, World!');",
+        )?;
+        let res = assert_cache_untouched(|| {
+            crate::compile::bundle("no_cache_bundle.ln".to_string(), "main".to_string(), true)
+        });
+        std::fs::remove_file("no_cache_bundle.ln")?;
+        res?;
+        let run = std::process::Command::new("node")
+            .arg("no_cache_bundle.js")
+            .output()?;
+        std::fs::remove_file("no_cache_bundle.js")?;
+        std::fs::remove_file("no_cache_bundle.js.map")?;
+        assert_eq!(String::from_utf8(run.stdout)?, "Hello, World!\n");
+        Ok(())
+    }
+}
+
+// Project manifest
+
+#[cfg(test)]
+mod parse_manifest {
+    #[test]
+    fn parse_manifest() -> Result<(), Box<dyn std::error::Error>> {
+        let entries = crate::compile::parse_manifest(
+            r#"
+            # The name and entry function are optional
+            [[entry]]
+            file = "src/server.ln"
+
+            [[entry]]
+            file = "tools/migrate.ln"
+            name = "db-migrate" # Not `migrate`
+            fn = "run"
+            "#,
+        )?;
+        assert_eq!(
+            entries,
+            vec![
+                crate::compile::ManifestEntry {
+                    file: "src/server.ln".to_string(),
+                    name: "server".to_string(),
+                    entry_fn: "main".to_string(),
+                },
+                crate::compile::ManifestEntry {
+                    file: "tools/migrate.ln".to_string(),
+                    name: "db-migrate".to_string(),
+                    entry_fn: "run".to_string(),
+                },
+            ]
+        );
+        assert!(crate::compile::parse_manifest("").is_err());
+        assert!(crate::compile::parse_manifest("file = \"index.ln\"").is_err());
+        assert!(crate::compile::parse_manifest("[[entry]]\nname = \"index\"").is_err());
+        assert!(crate::compile::parse_manifest("[[entry]]\nfile = index.ln").is_err());
+        assert!(crate::compile::parse_manifest("[[entry]]\nfiel = \"index.ln\"").is_err());
+        assert!(crate::compile::parse_manifest(
+            "[[entry]]\nfile = \"a/index.ln\"\n[[entry]]\nfile = \"b/index.ln\""
+        )
+        .is_err());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod compile_project {
+    #[test]
+    fn compile_project() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join("alan_compile_project");
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("hello.ln"), "export fn main = print('Hello!');")?;
+        std::fs::write(dir.join("goodbye.ln"), "export fn run = print('Goodbye!');")?;
+        std::fs::write(
+            dir.join("alan.toml"),
+            "[[entry]]\nfile = \"hello.ln\"\n\n[[entry]]\nfile = \"goodbye.ln\"\nname = \"bye\"\nfn = \"run\"\n",
+        )?;
+        let res = crate::compile::compile_project(
+            dir.join("alan.toml").to_string_lossy().to_string(),
+            None,
+            false,
+            Vec::new(),
+        );
+        // Both binaries are written next to the manifest
+        let runs = ["hello", "bye"]
+            .iter()
+            .map(|name| std::process::Command::new(dir.join(name)).output())
+            .collect::<Result<Vec<std::process::Output>, std::io::Error>>();
+        std::fs::remove_dir_all(&dir)?;
+        res?;
+        let runs = runs?;
+        assert_eq!(String::from_utf8(runs[0].stdout.clone())?, "Hello!\n");
+        assert_eq!(String::from_utf8(runs[1].stdout.clone())?, "Goodbye!\n");
+        Ok(())
+    }
+}
+
+// Lockfile
+
+#[cfg(test)]
+mod lockfile_held {
+    #[test]
+    fn lockfile_held() -> Result<(), Box<dyn std::error::Error>> {
+        let lockfile_path = std::env::temp_dir().join("alan_lockfile_held.lockfile");
+        std::fs::write(&lockfile_path, "0")?;
+        // Take the lock through a separate handle, as another build would
+        let holder = std::fs::File::open(&lockfile_path)?;
+        crate::compile::acquire_file_lock(
+            &holder,
+            &lockfile_path,
+            std::time::Duration::from_secs(1),
+        )?;
+        let waiter = std::fs::File::open(&lockfile_path)?;
+        let res = crate::compile::acquire_file_lock(
+            &waiter,
+            &lockfile_path,
+            std::time::Duration::from_millis(200),
+        );
+        // Spelled out, as newer versions of Rust also have a `File::unlock` method
+        fs2::FileExt::unlock(&holder)?;
+        std::fs::remove_file(&lockfile_path)?;
+        std::fs::remove_file(lockfile_path.with_extension("pid"))?;
+        match res {
+            Ok(_) => Err("Unexpectedly acquired the lock".into()),
+            Err(e) => {
+                let msg = format!("{}", e);
+                assert!(msg.contains(&format!("held by process {}", std::process::id())));
+                assert!(msg.contains("still running"));
+                Ok(())
+            }
+        }
+    }
+}
+
+// Build output
+
+#[cfg(test)]
+mod remove_if_exists {
+    #[test]
+    fn remove_if_exists() -> Result<(), Box<dyn std::error::Error>> {
+        let path = std::env::temp_dir().join("alan_remove_if_exists_bin");
+        std::fs::write(&path, "binary")?;
+        crate::compile::remove_if_exists(&path)?;
+        assert!(!path.exists());
+        // A missing prior binary is not an error
+        crate::compile::remove_if_exists(&path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod missing_target {
+    #[test]
+    fn missing_target() -> Result<(), Box<dyn std::error::Error>> {
+        match crate::compile::check_target_installed("not-a-real-target") {
+            Ok(_) => Err("Unexpectedly found the target".into()),
+            Err(e) => {
+                assert!(format!("{}", e).contains("not-a-real-target"));
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod cross_compile {
+    #[test]
+    fn cross_compile() -> Result<(), Box<dyn std::error::Error>> {
+        // Cross-compiling needs the target (and a linker for it) to be installed, so this only
+        // runs where that has been set up
+        let triple = "aarch64-unknown-linux-gnu";
+        if crate::compile::check_target_installed(triple).is_err() {
+            return Ok(());
+        }
+        alan_compiler::program::Program::set_target_lang_rs();
+        std::fs::write(
+            "cross_compile.ln",
+            "export fn main = print('Hello, World!');",
+        )?;
+        let res = crate::compile::compile(
+            "cross_compile.ln".to_string(),
+            "main".to_string(),
+            Some(triple.to_string()),
+            false,
+            Vec::new(),
+        );
+        std::fs::remove_file("cross_compile.ln")?;
+        res?;
+        std::fs::remove_file("cross_compile")?;
+        let bin_path = crate::compile::cache_dir(std::env::var_os("ALAN_CACHE_DIR"))
+            .join("alan_generated_bin")
+            .join("target")
+            .join(triple)
+            .join("release")
+            .join("alan_generated_bin");
+        assert!(bin_path.exists());
+        Ok(())
+    }
+}
+
+// Window rendering
+
+#[cfg(test)]
+mod window_aux_buffers {
+    // Opening a window can't be done in the test environment, so this only checks that a two pass
+    // chain through an auxiliary buffer compiles for both targets
+    const SRC: &str = r#"export fn main {
+  window(fn (w: Mut{Window}) = w.auxBuffers(1.u32), fn (w: Mut{Window}) = [w.width, w.height, w.bufferWidth], fn (frame: Frame) {
+    const aux = frame.aux(0)!!;
+    const size = {i64[3]}(-1, -2, 1);
+    return [
+      GPGPU("
+        @group(0) @binding(0) var<storage, read> ctx: array<u32>;
+        @group(0) @binding(1) var<storage, read_write> auxbuf: array<u32>;
+        @compute @workgroup_size(1)
+        fn main(@builtin(global_invocation_id) id: vec3u) {
+          auxbuf[id.x + id.y * ctx[2]] = 0xff0000ffu;
+        }
+      ", [[frame.context, aux]], size),
+      GPGPU("
+        @group(0) @binding(0) var<storage, read> ctx: array<u32>;
+        @group(0) @binding(1) var<storage, read> auxbuf: array<u32>;
+        @group(0) @binding(2) var<storage, read_write> fb: array<u32>;
+        @compute @workgroup_size(1)
+        fn main(@builtin(global_invocation_id) id: vec3u) {
+          let i = id.x + id.y * ctx[2];
+          fb[i] = auxbuf[i];
+        }
+      ", [[frame.context, aux, frame.framebuffer]], size)
+    ];
+  });
+}"#;
+
+    #[test]
+    fn window_aux_buffers() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::compile::{compile_str, Target};
+        let rs = compile_str("window_aux_buffers.ln", SRC, Target::Rs)?;
+        assert!(rs.contains(".aux_buffers("));
+        assert!(rs.contains(".aux_buffer("));
+        let js = compile_str("window_aux_buffers.ln", SRC, Target::Js)?;
+        assert!(js.contains("alan_std.contextAuxBuffers"));
+        assert!(js.contains("alan_std.frameAux"));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod window_surface_format {
+    const SRC: &str = r#"export fn main {
+  window(fn (w: Mut{Window}) = w.surfaceFormat('rgba16float'), fn (w: Mut{Window}) = [w.width, w.height, w.bufferWidth], fn (frame: Frame) {
+    return [
+      GPGPU("
+        @group(0) @binding(0) var<storage, read> ctx: array<u32>;
+        @group(0) @binding(1) var<storage, read_write> fb: array<u32>;
+        @compute @workgroup_size(1)
+        fn main(@builtin(global_invocation_id) id: vec3u) {
+          fb[id.x + id.y * ctx[2]] = 0xff0000ffu;
+        }
+      ", [[frame.context, frame.framebuffer]], {i64[3]}(-1, -2, 1))
+    ];
+  });
+}"#;
+
+    #[test]
+    fn window_surface_format() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::compile::{compile_str, Target};
+        let rs = compile_str("window_surface_format.ln", SRC, Target::Rs)?;
+        assert!(rs.contains(".surface_format("));
+        let js = compile_str("window_surface_format.ln", SRC, Target::Js)?;
+        assert!(js.contains("alan_std.contextSurfaceFormat"));
+        Ok(())
+    }
+}
+
+// Rendering a frame straight to a file doesn't need a window, so this can run wherever there is a
+// GPU. The frame is narrower than the 64 pixel row alignment, so the padding is dropped. Each pixel
+// is written as a little-endian u32, so the lowest byte is the red channel
+test_gpgpu!(window_save_frame => r#"
+    export fn main {
+      renderFrame(fn (w: Mut{Window}) = w.initialSize(3.u32, 2.u32), fn (w: Mut{Window}) = [w.width, w.height, w.bufferWidth], fn (frame: Frame) {
+        return [
+          GPGPU("
+            @group(0) @binding(0) var<storage, read> ctx: array<u32>;
+            @group(0) @binding(1) var<storage, read_write> fb: array<u32>;
+            @compute @workgroup_size(1)
+            fn main(@builtin(global_invocation_id) id: vec3u) {
+              fb[id.x + id.y * ctx[2]] = 0xff804020u;
+            }
+          ", [[frame.context, frame.framebuffer]], {i64[3]}(-1, -2, 1))
+        ];
+      }, 'window_save_frame.png').failed.print;
+    }"#;
+    stdout_rs "false\n";
+    // There's no file system to save the frame to in the browser
+    stdout_js "true\n";
+    frame_rs ("window_save_frame.png", (3, 2), [0x20, 0x40, 0x80, 0xff]);
+);
+
+// Generated types
+
+#[cfg(test)]
+mod generated_type_derives {
+    #[test]
+    fn generated_type_derives() -> Result<(), Box<dyn std::error::Error>> {
+        let rs = crate::compile::compile_str(
+            "generated_type_derives.ln",
+            r#"type Point =
+  x: f64,
+  y: f64;
+
+type IntOrStr = i64 | string;
+
+export fn main {
+  const p = Point(1.0, 2.0);
+  p.x.print;
+  const v = IntOrStr("It's a string!");
+  v.string.exists.print;
+}"#,
+            crate::compile::Target::Rs,
+        )?;
+        assert!(rs.contains("#[derive(Clone, Debug, PartialEq)]\nenum"));
+        assert!(!rs.contains("#[derive(Clone)]\nenum"));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod array_literal_clones {
+    #[test]
+    fn array_literal_clones() -> Result<(), Box<dyn std::error::Error>> {
+        let rs = crate::compile::compile_str(
+            "array_literal_clones.ln",
+            r#"export fn main {
+  const n = 5;
+  const flag = true;
+  const v = [1, 2];
+  [n, n].print;
+  [flag, false].print;
+  [v, v].len.print;
+  v.print;
+}"#,
+            crate::compile::Target::Rs,
+        )?;
+        // Only the variables that would otherwise be moved into the array are cloned
+        assert!(rs.contains("Vec::<i64>::from([n, n])"));
+        assert!(rs.contains("vec![flag, false]"));
+        assert!(rs.contains("vec![v.clone(), v.clone()]"));
+        Ok(())
+    }
+}
+
+// Shader validation
+
+#[cfg(test)]
+mod invalid_wgsl_shader {
+    #[test]
+    fn invalid_wgsl_shader() -> Result<(), Box<dyn std::error::Error>> {
+        let res = crate::compile::compile_str(
+            "invalid_wgsl_shader.ln",
+            r#"export fn main {
+  let b = GBuffer(filled(2.i32, 4));
+  GPGPU("
+    @compute
+    @workgroup_size(1)
+    fn main() {
+      let x: i32 = ;
+    }
+  ", b).run;
+}"#,
+            crate::compile::Target::Rs,
+        );
+        match res {
+            Ok(_) => Err("Unexpectedly succeeded!".into()),
+            Err(e) => Ok(assert!(
+                format!("{}", e).starts_with("Invalid WGSL shader at invalid_wgsl_shader.ln:7: ")
+            )),
+        }
+    }
+
+    #[test]
+    fn skip_shader_validation() -> Result<(), Box<dyn std::error::Error>> {
+        use alan_compiler::program::Program;
+        Program::set_target_lang_rs();
+        let mut program = Program::get_program();
+        program
+            .env
+            .insert("ALAN_VALIDATE_SHADERS".to_string(), "false".to_string());
+        Program::return_program(program);
+        let res = crate::compile::compile_str(
+            "skip_shader_validation.ln",
+            r#"export fn main {
+  let b = GBuffer(filled(2.i32, 4));
+  GPGPU("not a shader", b).run;
+}"#,
+            crate::compile::Target::Rs,
+        );
+        let mut program = Program::get_program();
+        program.env.remove("ALAN_VALIDATE_SHADERS");
+        Program::return_program(program);
+        res?;
+        Ok(())
+    }
+}
+
 // Module-level constants
 
 test!(module_level_constant => r#"
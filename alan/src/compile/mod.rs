@@ -1,23 +1,242 @@
-use std::env::current_dir;
-use std::fs::{create_dir_all, remove_file, write, File};
+use std::collections::{BTreeMap, HashSet};
+use std::env::{current_dir, temp_dir};
+use std::ffi::OsString;
+use std::fs::{
+    canonicalize, copy, create_dir_all, read_to_string, remove_dir_all, remove_file, write, File,
+};
 use std::io::Read;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use dirs::config_dir;
 use fs2::FileExt;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
 
-use alan_compiler::lntojs::lntojs;
-use alan_compiler::lntors::lntors;
-use alan_compiler::program::Program;
+use alan_compiler::lntojs::{lntojs, lntojs_with_sourcemap};
+use alan_compiler::lntors::{lntors, lntors_module, lntors_staticlib};
+use alan_compiler::program::{FnKind, Microstatement, Program, Scope};
 
 mod integration_tests;
 
+/// The `cache_dir` function returns the directory the build caches are kept in. The `ALAN_CACHE_DIR`
+/// environment variable takes precedence, otherwise it is an `alan` directory within the user's
+/// configuration directory, or within the temporary directory for headless environments that have
+/// no configuration directory.
+pub(crate) fn cache_dir(env_override: Option<OsString>) -> PathBuf {
+    match env_override {
+        Some(d) if !d.is_empty() => PathBuf::from(d),
+        _ => {
+            // All this because `push` is not chainable :/
+            let mut a = config_dir().unwrap_or_else(temp_dir);
+            a.push("alan");
+            a
+        }
+    }
+}
+
+/// The `uncached_dir` function returns a fresh directory to build in, for hermetic builds that
+/// ignore the shared build cache. It is unique to this process and moment, so it never collides
+/// with another build.
+pub(crate) fn uncached_dir() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let mut d = temp_dir();
+    d.push(format!(
+        "alan_uncached_{}_{}",
+        std::process::id(),
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos()
+    ));
+    Ok(d)
+}
+
+/// How long to wait for a concurrent build to release the lockfile, unless overridden (in seconds)
+/// by the `ALAN_LOCK_TIMEOUT` environment variable.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(180);
+
+fn lock_timeout() -> Duration {
+    match std::env::var("ALAN_LOCK_TIMEOUT") {
+        Ok(s) => match s.parse::<u64>() {
+            Ok(secs) => Duration::from_secs(secs),
+            Err(_) => LOCK_TIMEOUT,
+        },
+        Err(_) => LOCK_TIMEOUT,
+    }
+}
+
+/// The `process_running` function checks if a process with the given ID still exists.
+fn process_running(pid: u32) -> bool {
+    if cfg!(windows) {
+        matches!(
+            Command::new("tasklist").arg("/FI").arg(format!("PID eq {}", pid)).output(),
+            Ok(o) if String::from_utf8_lossy(&o.stdout).contains(&pid.to_string())
+        )
+    } else {
+        matches!(
+            Command::new("kill").arg("-0").arg(pid.to_string()).output(),
+            Ok(o) if o.status.success()
+        )
+    }
+}
+
+/// The `acquire_file_lock` function takes the exclusive lock on the lockfile, waiting up to the
+/// timeout for another Alan compile to release it. The lockfile itself records when the
+/// dependencies were last updated, so the ID of the process holding the lock is written next to
+/// it, with a `.pid` extension, to be able to report who is holding it if the timeout is reached.
+pub(crate) fn acquire_file_lock(
+    lockfile: &File,
+    lockfile_path: &Path,
+    timeout: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pid_path = lockfile_path.with_extension("pid");
+    let sleep_time = Duration::from_millis(100);
+    let expiry = Instant::now() + timeout;
+    loop {
+        if lockfile.try_lock_exclusive().is_ok() {
+            break;
+        }
+        if Instant::now() >= expiry {
+            let holder = std::fs::read_to_string(&pid_path)
+                .ok()
+                .and_then(|s| s.trim().parse::<u32>().ok());
+            return Err(match holder {
+                Some(pid) => format!(
+                    "Could not acquire the lockfile within {}sec. It is held by process {}, which is {}",
+                    timeout.as_secs(),
+                    pid,
+                    if process_running(pid) {
+                        "still running"
+                    } else {
+                        "no longer running"
+                    }
+                ),
+                None => format!(
+                    "Could not acquire the lockfile within {}sec",
+                    timeout.as_secs()
+                ),
+            }
+            .into());
+        }
+        std::thread::sleep(sleep_time);
+    }
+    if let Err(e) = write(&pid_path, std::process::id().to_string()) {
+        lockfile.unlock()?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// The `remove_if_exists` function deletes the specified file, treating a file that is already gone
+/// as success.
+pub(crate) fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match remove_file(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        r => r,
+    }
+}
+
+/// The `check_target_installed` function confirms the specified target triple has been installed
+/// with rustup, so a cross-compilation fails with a clear error instead of a wall of cargo output.
+pub(crate) fn check_target_installed(triple: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let installed = match Command::new("rustup")
+        .arg("target")
+        .arg("list")
+        .arg("--installed")
+        .output()
+    {
+        Ok(o) if o.status.success() => String::from_utf8(o.stdout)?,
+        _ => {
+            return Err(format!(
+                "rustup not found. It is required to compile for the {} target",
+                triple
+            )
+            .into())
+        }
+    };
+    if installed.lines().any(|l| l.trim() == triple) {
+        Ok(())
+    } else {
+        Err(format!(
+            "The {0} target is not installed. Install it with `rustup target add {0}`",
+            triple
+        )
+        .into())
+    }
+}
+
 /// The `build` function creates a temporary directory that is a Cargo project primarily consisting
 /// of a single source file, plus a Cargo.toml file including the 3rd party dependencies in the
 /// standard library and user source code.
-pub fn build(source_file: String) -> Result<String, Box<dyn std::error::Error>> {
+pub fn build(source_file: String, entry_fn: String) -> Result<String, Box<dyn std::error::Error>> {
+    build_for_target(source_file, entry_fn, None, false, &[])
+}
+
+/// The `build_for_target` function is `build` with an optional target triple to cross-compile for,
+/// instead of the host. If `no_cache` is set, the shared build cache is neither read nor written,
+/// and the build happens in a fresh directory that is deleted afterwards. The `features` are
+/// `crate/feature` pairs to enable on the dependencies in the generated `Cargo.toml`.
+pub fn build_for_target(
+    source_file: String,
+    entry_fn: String,
+    target_triple: Option<String>,
+    no_cache: bool,
+    features: &[String],
+) -> Result<String, Box<dyn std::error::Error>> {
+    build_to(
+        source_file,
+        entry_fn,
+        target_triple,
+        no_cache,
+        features,
+        None,
+    )
+}
+
+/// The `build_to` function is `build_for_target` with an optional path to write the binary to,
+/// instead of a file named for the source file in the current directory.
+fn build_to(
+    source_file: String,
+    entry_fn: String,
+    target_triple: Option<String>,
+    no_cache: bool,
+    features: &[String],
+    out_path: Option<PathBuf>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if no_cache {
+        let dir = uncached_dir()?;
+        let res = build_in(
+            dir.clone(),
+            source_file,
+            entry_fn,
+            target_triple,
+            features,
+            out_path,
+        );
+        if dir.exists() {
+            remove_dir_all(&dir)?;
+        }
+        res
+    } else {
+        build_in(
+            cache_dir(std::env::var_os("ALAN_CACHE_DIR")),
+            source_file,
+            entry_fn,
+            target_triple,
+            features,
+            out_path,
+        )
+    }
+}
+
+/// The `build_in` function does the actual work of `build`, using the specified directory for the
+/// build cache.
+fn build_in(
+    alan_config: PathBuf,
+    source_file: String,
+    entry_fn: String,
+    target_triple: Option<String>,
+    features: &[String],
+    out_path: Option<PathBuf>,
+) -> Result<String, Box<dyn std::error::Error>> {
     let find_process = if cfg!(windows) { "where" } else { "which" };
     // Fail if rustc is not present
     match Command::new(find_process).arg("rustc").output() {
@@ -33,6 +252,10 @@ pub fn build(source_file: String) -> Result<String, Box<dyn std::error::Error>>
             Err("cargo not found. Please make sure you have rust installed before using Alan!")
         }
     }?;
+    // And fail if cross-compiling for a target that can't be built
+    if let Some(triple) = &target_triple {
+        check_target_installed(triple)?;
+    }
     // Because all Alan programs use the same Rust dependencies (for now), we can cut down a *lot*
     // of build time by re-using the `./target/release/build` and `./target/release/deps` directory
     // in subsequent builds. Since it takes over 30 seconds to make a release build on my laptop
@@ -43,16 +266,6 @@ pub fn build(source_file: String) -> Result<String, Box<dyn std::error::Error>>
     // if another Alan compile is concurrently running, if so, we sleep wait until it is gone
     // (either the lockfile is deleted or the process ID in the lockfile is no longer running and
     // then we delete it and continue. Then we continue with the regular build flow.
-    let config_dir = match config_dir() {
-        Some(c) => Ok(c),
-        None => Err("Somehow no configuration directory exists on this operating system"),
-    }?;
-    let alan_config = {
-        // All this because `push` is not chainable :/
-        let mut a = config_dir.clone();
-        a.push("alan");
-        a
-    };
     let lockfile_path = {
         let mut l = alan_config.clone();
         l.push(".lockfile");
@@ -63,9 +276,13 @@ pub fn build(source_file: String) -> Result<String, Box<dyn std::error::Error>>
         p.push("alan_generated_bin");
         p
     };
+    // Cross-compiled binaries are placed in a subdirectory named for the target triple
     let release_path = {
         let mut r = project_dir.clone();
         r.push("target");
+        if let Some(triple) = &target_triple {
+            r.push(triple);
+        }
         r.push("release");
         r
     };
@@ -92,28 +309,7 @@ edition = "2021"
         )?;
     }
     let mut lockfile = File::open(lockfile_path.as_path())?;
-    if cfg!(windows) {
-        let timeout = std::time::Duration::from_secs(180);
-        let sleep_time = std::time::Duration::from_millis(100);
-        let mut now = std::time::Instant::now();
-        let expiry = now + timeout;
-        let mut locked = false;
-        while now < expiry {
-            match lockfile.lock_exclusive() {
-                Err(_) => std::thread::sleep(sleep_time),
-                Ok(_) => {
-                    locked = true;
-                    break;
-                }
-            }
-            now = std::time::Instant::now();
-        }
-        if !locked {
-            return Err("Could not lock the lockfile".into());
-        }
-    } else {
-        lockfile.lock_exclusive()?;
-    }
+    acquire_file_lock(&lockfile, &lockfile_path, lock_timeout())?;
     let should_rebuild_deps = {
         let mut b = Vec::new();
         lockfile.read_to_end(&mut b)?;
@@ -158,13 +354,14 @@ edition = "2021"
                 Err(e)
             }
         }?;
-        let (rs_str, deps) = match lntors(hello_path.to_string_lossy().to_string()) {
-            Ok(a) => Ok(a),
-            Err(e) => {
-                lockfile.unlock()?;
-                Err(e)
-            }
-        }?;
+        let (rs_str, deps) =
+            match lntors(hello_path.to_string_lossy().to_string(), "main".to_string()) {
+                Ok(a) => Ok(a),
+                Err(e) => {
+                    lockfile.unlock()?;
+                    Err(e)
+                }
+            }?;
         match write(
             cargo_path.clone(),
             format!(
@@ -234,14 +431,12 @@ edition = "2021"
     }
     // We need to remove the prior binary, if it exists, to prevent a prior successful compilation
     // from accidentally being treated as the output of an unsuccessful compilation.
-    match Command::new("rm")
-        .current_dir(release_path.clone())
-        .arg("-f")
-        .arg("alan_generated_bin")
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-    {
+    let bin_path = {
+        let mut b = release_path.clone();
+        b.push("alan_generated_bin");
+        b
+    };
+    match remove_if_exists(&bin_path) {
         Ok(a) => Ok(a),
         Err(e) => {
             lockfile.unlock()?;
@@ -257,7 +452,14 @@ edition = "2021"
         s
     };
     // Generate the rust code to compile
-    let (rs_str, deps) = match lntors(source_file.clone()) {
+    let (rs_str, deps) = match lntors(source_file.clone(), entry_fn) {
+        Ok(s) => Ok(s),
+        Err(e) => {
+            lockfile.unlock()?;
+            Err(e)
+        }
+    }?;
+    let deps_str = match cargo_deps(deps.iter(), features) {
         Ok(s) => Ok(s),
         Err(e) => {
             lockfile.unlock()?;
@@ -266,29 +468,7 @@ edition = "2021"
     }?;
     // Always write the `Cargo.toml` file, in case the cache is out-of-date from a prior version of
     // the Alan compiler is still present.
-    match write(
-        cargo_path.clone(),
-        format!(
-            "{}\n{}",
-            cargo_str,
-            deps.iter()
-                .map(|(k, v)| {
-                    let parts = v.split("#").collect::<Vec<&str>>();
-                    if parts.len() == 2 {
-                        format!(
-                            "{} = {{ git = \"{}\", branch = \"{}\" }}",
-                            k, parts[0], parts[1]
-                        )
-                    } else {
-                        // We'll assume there's only one part, since the alternative
-                        // wouldn't parse properly. If it blows up, it's on them.
-                        format!("{} = {{ git = \"{}\" }}", k, v)
-                    }
-                })
-                .collect::<Vec<String>>()
-                .join("\n")
-        ),
-    ) {
+    match write(cargo_path.clone(), format!("{}\n{}", cargo_str, deps_str)) {
         Ok(a) => Ok(a),
         Err(e) => {
             lockfile.unlock()?;
@@ -339,10 +519,15 @@ edition = "2021"
         }
     }
     // Build the executable
-    match Command::new("cargo")
+    let mut cargo_build = Command::new("cargo");
+    cargo_build
         .current_dir(project_dir.clone())
         .arg("build")
-        .arg("--release")
+        .arg("--release");
+    if let Some(triple) = &target_triple {
+        cargo_build.arg("--target").arg(triple);
+    }
+    match cargo_build
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .output()
@@ -367,18 +552,15 @@ edition = "2021"
         None => panic!("Somehow can't parse the source file name as a path?"),
         Some(n) => n.to_string_lossy().to_string(),
     };
-    match Command::new("cp")
-        .current_dir(release_path)
-        .arg("alan_generated_bin")
-        .arg(format!(
-            "{}/{}",
-            current_dir()?.to_string_lossy(),
-            project_name_str
-        ))
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
-    {
+    let out_path = match out_path {
+        Some(o) => o,
+        None => {
+            let mut o = current_dir()?;
+            o.push(&project_name_str);
+            o
+        }
+    };
+    match copy(&bin_path, out_path) {
         Ok(a) => Ok(a),
         Err(e) => {
             lockfile.unlock()?;
@@ -391,8 +573,16 @@ edition = "2021"
 }
 
 /// The `compile` function is a thin wrapper on top of `build` that builds an executable in release
-/// mode and exits, printing the time it took to run on success.
-pub fn compile(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
+/// mode, for the target triple if provided, and exits, printing the time it took to run on success.
+/// If `no_cache` is set the shared build cache is skipped, for hermetic builds. The `features` are
+/// enabled on the dependencies of the generated `Cargo.toml`.
+pub fn compile(
+    source_file: String,
+    entry_fn: String,
+    target_triple: Option<String>,
+    no_cache: bool,
+    features: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
     let start_time = Instant::now();
     Program::set_target_lang_rs();
     let mut program = Program::get_program();
@@ -400,7 +590,169 @@ pub fn compile(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
         .env
         .insert("ALAN_TARGET".to_string(), "release".to_string());
     Program::return_program(program);
-    build(source_file)?;
+    build_for_target(source_file, entry_fn, target_triple, no_cache, &features)?;
+    println!("Done! Took {:.2}sec", start_time.elapsed().as_secs_f32());
+    Ok(())
+}
+
+/// The name of the project manifest `alan compile` builds when it is not given a source file
+pub const MANIFEST_FILE: &str = "alan.toml";
+
+/// An entry point declared in an `alan.toml` project manifest: the source file to build, the name
+/// of the binary to produce, and the exported function to use as the entry point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ManifestEntry {
+    pub file: String,
+    pub name: String,
+    pub entry_fn: String,
+}
+
+/// The `parse_manifest` function reads the entry points out of an `alan.toml` manifest. Only the
+/// subset of TOML that the manifest needs is supported: `[[entry]]` tables of `key = "value"`
+/// lines for the `file` to build and, optionally, the `name` of the binary and the entry `fn`, plus
+/// comments and blank lines. The name defaults to the file name without its extension, and the
+/// entry function to `main`.
+pub(crate) fn parse_manifest(src: &str) -> Result<Vec<ManifestEntry>, Box<dyn std::error::Error>> {
+    let mut tables: Vec<BTreeMap<String, String>> = Vec::new();
+    for (i, line) in src.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line == "[[entry]]" {
+            tables.push(BTreeMap::new());
+            continue;
+        }
+        if tables.is_empty() {
+            return Err(format!(
+                "{} line {}: Expected an `[[entry]]` table",
+                MANIFEST_FILE,
+                i + 1
+            )
+            .into());
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => {
+                return Err(format!(
+                    "{} line {}: Expected `key = \"value\"`",
+                    MANIFEST_FILE,
+                    i + 1
+                )
+                .into())
+            }
+        };
+        let value = match value.strip_prefix('"').and_then(|v| v.split_once('"')) {
+            Some((v, rest)) if rest.trim().is_empty() || rest.trim().starts_with('#') => v,
+            _ => {
+                return Err(format!(
+                    "{} line {}: The value of `{}` must be a string",
+                    MANIFEST_FILE,
+                    i + 1,
+                    key
+                )
+                .into())
+            }
+        };
+        if !["file", "name", "fn"].contains(&key) {
+            return Err(format!(
+                "{} line {}: Unknown key `{}`, expected `file`, `name`, or `fn`",
+                MANIFEST_FILE,
+                i + 1,
+                key
+            )
+            .into());
+        }
+        if let Some(table) = tables.last_mut() {
+            if table.insert(key.to_string(), value.to_string()).is_some() {
+                return Err(format!(
+                    "{} line {}: `{}` is set more than once for this entry",
+                    MANIFEST_FILE,
+                    i + 1,
+                    key
+                )
+                .into());
+            }
+        }
+    }
+    if tables.is_empty() {
+        return Err(format!("{} does not declare any `[[entry]]` tables", MANIFEST_FILE).into());
+    }
+    let mut entries: Vec<ManifestEntry> = Vec::new();
+    for (i, mut table) in tables.into_iter().enumerate() {
+        let file = match table.remove("file") {
+            Some(f) => f,
+            None => {
+                return Err(format!("{} entry {} has no `file`", MANIFEST_FILE, i + 1).into());
+            }
+        };
+        let name = match table.remove("name") {
+            Some(n) => n,
+            None => match Path::new(&file).file_stem() {
+                Some(s) => s.to_string_lossy().to_string(),
+                None => {
+                    return Err(format!(
+                        "{} entry {} has no usable file name",
+                        MANIFEST_FILE,
+                        i + 1
+                    )
+                    .into());
+                }
+            },
+        };
+        // Every binary is written to the same directory, so they would overwrite each other
+        if entries.iter().any(|e| e.name == name) {
+            return Err(format!(
+                "{} declares more than one entry named `{}`",
+                MANIFEST_FILE, name
+            )
+            .into());
+        }
+        let entry_fn = table.remove("fn").unwrap_or_else(|| "main".to_string());
+        entries.push(ManifestEntry {
+            file,
+            name,
+            entry_fn,
+        });
+    }
+    Ok(entries)
+}
+
+/// The `compile_project` function builds every entry point declared in the specified `alan.toml`
+/// manifest, the same way `compile` builds a single file. The paths in the manifest are relative to
+/// the directory it is in, which is also where the binaries are written.
+pub fn compile_project(
+    manifest: String,
+    target_triple: Option<String>,
+    no_cache: bool,
+    features: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start_time = Instant::now();
+    let entries = parse_manifest(&read_to_string(&manifest)?)?;
+    let root = {
+        let mut r = current_dir()?;
+        if let Some(dir) = Path::new(&manifest).parent() {
+            r.push(dir);
+        }
+        r
+    };
+    Program::set_target_lang_rs();
+    let mut program = Program::get_program();
+    program
+        .env
+        .insert("ALAN_TARGET".to_string(), "release".to_string());
+    Program::return_program(program);
+    for entry in entries {
+        build_to(
+            root.join(&entry.file).to_string_lossy().to_string(),
+            entry.entry_fn,
+            target_triple.clone(),
+            no_cache,
+            &features,
+            Some(root.join(&entry.name)),
+        )?;
+        println!("Built {}", entry.name);
+    }
     println!("Done! Took {:.2}sec", start_time.elapsed().as_secs_f32());
     Ok(())
 }
@@ -419,7 +771,7 @@ pub fn test(source_file: String, js: bool) -> Result<(), Box<dyn std::error::Err
         .insert("ALAN_TARGET".to_string(), "test".to_string());
     Program::return_program(program);
     if js {
-        let jsfile = web(source_file)?;
+        let jsfile = web(source_file, "main".to_string())?;
         let mut run = Command::new("node")
             .current_dir(current_dir()?)
             .arg(format!("{}.js", jsfile))
@@ -427,29 +779,20 @@ pub fn test(source_file: String, js: bool) -> Result<(), Box<dyn std::error::Err
             .stderr(Stdio::inherit())
             .spawn()?;
         let ecode = run.wait()?;
-        Command::new("rm")
-            .current_dir(current_dir()?)
-            .arg(format!("{}.js", jsfile))
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
+        remove_file(format!("{}.js", jsfile))?;
+        remove_file(format!("{}.js.map", jsfile))?;
         if !ecode.success() {
             std::process::exit(ecode.code().unwrap());
         }
     } else {
-        let binary = build(source_file)?;
+        let binary = build(source_file, "main".to_string())?;
         let mut run = Command::new(format!("./{}", binary))
             .current_dir(current_dir()?)
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
             .spawn()?;
         let ecode = run.wait()?;
-        Command::new("rm")
-            .current_dir(current_dir()?)
-            .arg(binary)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
+        remove_file(binary)?;
         if !ecode.success() {
             std::process::exit(ecode.code().unwrap());
         }
@@ -460,7 +803,40 @@ pub fn test(source_file: String, js: bool) -> Result<(), Box<dyn std::error::Err
 /// The `web` function creates a temporary directory that is an NPM project, primarily consisting
 /// of a single source file, plus a package.json file including third party dependencies in the
 /// standard library and user source code.
-pub fn web(source_file: String) -> Result<String, Box<dyn std::error::Error>> {
+pub fn web(source_file: String, entry_fn: String) -> Result<String, Box<dyn std::error::Error>> {
+    web_with(source_file, entry_fn, false)
+}
+
+/// The `web_with` function is `web` with the option to skip the shared build cache. If `no_cache`
+/// is set, the npm project is created in a fresh directory that is deleted afterwards.
+pub fn web_with(
+    source_file: String,
+    entry_fn: String,
+    no_cache: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if no_cache {
+        let dir = uncached_dir()?;
+        let res = web_in(dir.clone(), source_file, entry_fn);
+        if dir.exists() {
+            remove_dir_all(&dir)?;
+        }
+        res
+    } else {
+        web_in(
+            cache_dir(std::env::var_os("ALAN_CACHE_DIR")),
+            source_file,
+            entry_fn,
+        )
+    }
+}
+
+/// The `web_in` function does the actual work of `web`, using the specified directory for the
+/// build cache.
+fn web_in(
+    alan_config: PathBuf,
+    source_file: String,
+    entry_fn: String,
+) -> Result<String, Box<dyn std::error::Error>> {
     let find_process = if cfg!(windows) { "where" } else { "which" };
     // Fail if node is not present
     match Command::new(find_process).arg("node").output() {
@@ -478,16 +854,6 @@ pub fn web(source_file: String) -> Result<String, Box<dyn std::error::Error>> {
     }?;
     let has_yarn =
         matches!(Command::new(find_process).arg("yarn").output(), Ok(a) if !a.stdout.is_empty());
-    let config_dir = match config_dir() {
-        Some(c) => Ok(c),
-        None => Err("Somehow no configuration directory exists on this operating system"),
-    }?;
-    let alan_config = {
-        // All this because `push` is not chainable :/
-        let mut a = config_dir.clone();
-        a.push("alan");
-        a
-    };
     let lockfile_path = {
         let mut l = alan_config.clone();
         l.push(".lockfile");
@@ -519,31 +885,7 @@ pub fn web(source_file: String) -> Result<String, Box<dyn std::error::Error>> {
         }?;
     }
     let lockfile = File::open(lockfile_path.as_path())?;
-    if cfg!(windows) {
-        let timeout = std::time::Duration::from_secs(180);
-        let sleep_time = std::time::Duration::from_millis(100);
-        let mut now = std::time::Instant::now();
-        let expiry = now + timeout;
-        let mut locked = false;
-        while now < expiry {
-            match lockfile.lock_exclusive() {
-                Err(_) => std::thread::sleep(sleep_time),
-                Ok(_) => {
-                    locked = true;
-                    break;
-                }
-            }
-            now = std::time::Instant::now();
-        }
-        if !locked {
-            return Err("Could not lock the lockfile".into());
-        }
-    } else {
-        match lockfile.lock_exclusive() {
-            Ok(a) => Ok(a),
-            Err(e) => Err(format!("Could not acquire the lock {:?}", e)),
-        }?;
-    }
+    acquire_file_lock(&lockfile, &lockfile_path, lock_timeout())?;
     if first_time || !project_dir.exists() {
         // First time initialization of the alan config directory
         match create_dir_all(project_dir.clone()) {
@@ -570,7 +912,8 @@ pub fn web(source_file: String) -> Result<String, Box<dyn std::error::Error>> {
         }
     }?;
     // Generate the js code to bundle
-    let (js_str, deps) = match lntojs(source_file.clone()) {
+    let (js_str, map_str, deps) = match lntojs_with_sourcemap(source_file.clone(), entry_fn, false)
+    {
         Ok(s) => Ok(s),
         Err(e) => {
             lockfile.unlock()?;
@@ -613,6 +956,62 @@ pub fn web(source_file: String) -> Result<String, Box<dyn std::error::Error>> {
             ))
         }
     }?;
+    // Rollup doesn't pick up the source map of its input on its own, so a config file with a small
+    // plugin loads it alongside the generated code. This lets the source map of the bundle point
+    // all the way back to the Alan source, relative to where the bundle is copied to.
+    let map_path = {
+        let mut m = project_dir.clone();
+        m.push("index.js.map");
+        m
+    };
+    let rollup_config_path = {
+        let mut c = project_dir.clone();
+        c.push("rollup.config.mjs");
+        c
+    };
+    match write(map_path, map_str).and_then(|_| {
+        write(
+            rollup_config_path,
+            format!(
+                r#"import {{ readFileSync }} from 'node:fs';
+import {{ dirname, relative, resolve }} from 'node:path';
+import nodeResolve from '@rollup/plugin-node-resolve';
+import terser from '@rollup/plugin-terser';
+
+const cwd = {:?};
+
+const alanSourceMap = {{
+  name: 'alan-source-map',
+  load(id) {{
+    if (id !== resolve('index.js')) return null;
+    const map = JSON.parse(readFileSync('index.js.map', 'utf8'));
+    map.sources = map.sources.map((s) => resolve(cwd, s));
+    return {{ code: readFileSync(id, 'utf8'), map }};
+  }},
+}};
+
+export default {{
+  input: 'index.js',
+  output: {{
+    file: 'bundle.js',
+    format: 'iife',
+    name: 'alanGeneratedBundle',
+    sourcemap: true,
+    sourcemapPathTransform: (s, mapPath) => relative(cwd, resolve(dirname(mapPath), s)),
+  }},
+  plugins: [alanSourceMap, nodeResolve(), terser()],
+}};
+"#,
+                current_dir()?.to_string_lossy(),
+            ),
+        )
+    }) {
+        Ok(a) => Ok(a),
+        Err(e) => {
+            lockfile.unlock()?;
+            Err(format!("Could not save the source map to disk {:?}", e))
+        }
+    }?;
     // Update the npm lockfile, if necessary
     match Command::new("rm")
         .current_dir(project_dir.clone())
@@ -654,34 +1053,16 @@ pub fn web(source_file: String) -> Result<String, Box<dyn std::error::Error>> {
         Command::new("node")
             .current_dir(project_dir.clone())
             .arg("./node_modules/rollup/dist/bin/rollup")
-            .arg("index.js")
-            .arg("--format")
-            .arg("iife")
-            .arg("--name")
-            .arg("alanGeneratedBundle")
-            .arg("-p")
-            .arg("@rollup/plugin-node-resolve")
-            .arg("-p")
-            .arg("@rollup/plugin-terser")
-            .arg("--file")
-            .arg("bundle.js")
+            .arg("--config")
+            .arg("rollup.config.mjs")
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .output()
     } else {
         Command::new("./node_modules/.bin/rollup")
             .current_dir(project_dir.clone())
-            .arg("index.js")
-            .arg("--format")
-            .arg("iife")
-            .arg("--name")
-            .arg("alanGeneratedBundle")
-            .arg("-p")
-            .arg("@rollup/plugin-node-resolve")
-            .arg("-p")
-            .arg("@rollup/plugin-terser")
-            .arg("--file")
-            .arg("bundle.js")
+            .arg("--config")
+            .arg("rollup.config.mjs")
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .output()
@@ -706,19 +1087,30 @@ pub fn web(source_file: String) -> Result<String, Box<dyn std::error::Error>> {
         None => panic!("Somehow can't parse the source file name as a path?"),
         Some(n) => n.to_string_lossy().to_string(),
     };
-    match Command::new("cp")
-        .current_dir(project_dir)
-        .arg("bundle.js")
-        .arg(format!(
-            "{}/{}.js",
-            current_dir()?.to_string_lossy(),
-            project_name_str
-        ))
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
+    // The bundle is renamed on the way, so its source map comment is renamed to match
+    let bundle_path = {
+        let mut b = project_dir.clone();
+        b.push("bundle.js");
+        b
+    };
+    let bundle_map_path = {
+        let mut b = project_dir.clone();
+        b.push("bundle.js.map");
+        b
+    };
+    match read_to_string(bundle_path)
+        .and_then(|b| {
+            write(
+                format!("{}.js", project_name_str),
+                b.replace(
+                    "//# sourceMappingURL=bundle.js.map",
+                    &format!("//# sourceMappingURL={}.js.map", project_name_str),
+                ),
+            )
+        })
+        .and_then(|_| copy(bundle_map_path, format!("{}.js.map", project_name_str)))
     {
-        Ok(a) => Ok(a),
+        Ok(_) => Ok(()),
         Err(e) => {
             lockfile.unlock()?;
             Err(format!(
@@ -733,8 +1125,13 @@ pub fn web(source_file: String) -> Result<String, Box<dyn std::error::Error>> {
 }
 
 /// The `bundle` function is a thin wrapper on top of `web` that builds an executable in release
-/// mode and exits, printing the time it took to run on success.
-pub fn bundle(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
+/// mode and exits, printing the time it took to run on success. If `no_cache` is set the shared
+/// build cache is skipped, for hermetic builds.
+pub fn bundle(
+    source_file: String,
+    entry_fn: String,
+    no_cache: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     let start_time = Instant::now();
     Program::set_target_lang_js();
     let mut program = Program::get_program();
@@ -742,14 +1139,175 @@ pub fn bundle(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
         .env
         .insert("ALAN_TARGET".to_string(), "release".to_string());
     Program::return_program(program);
-    web(source_file)?;
+    web_with(source_file, entry_fn, no_cache)?;
     println!("Done! Took {:.2}sec", start_time.elapsed().as_secs_f32());
     Ok(())
 }
 
+/// The `cargo_deps` function renders the dependencies returned by `lntors` as the body of the
+/// `[dependencies]` section of a `Cargo.toml` file. The dependencies are sorted by name so the
+/// same program always produces the same manifest, no matter what order they were found in,
+/// which would otherwise make cargo think the cached build is out-of-date. A dependency listed
+/// twice with different versions is an error. The `features` are `crate/feature` pairs, rendered
+/// as a sorted `features` array on the dependency they name, which must be one of the `deps`.
+pub(crate) fn cargo_deps<'a>(
+    deps: impl Iterator<Item = (&'a String, &'a String)>,
+    features: &[String],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut sorted = BTreeMap::new();
+    for (k, v) in deps {
+        match sorted.get(k) {
+            Some(prior) if *prior != v => {
+                return Err(format!(
+                    "Dependency {} has conflicting versions {} and {}",
+                    k, prior, v
+                )
+                .into());
+            }
+            _ => {
+                sorted.insert(k, v);
+            }
+        }
+    }
+    let mut crate_features: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for feature in features {
+        let (dep, name) = match feature.split_once('/') {
+            Some((d, n)) if !d.is_empty() && !n.is_empty() && !n.contains('/') => (d, n),
+            _ => {
+                return Err(
+                    format!("Feature {} must be in the form `crate/feature`", feature).into(),
+                );
+            }
+        };
+        if !sorted.keys().any(|k| k.as_str() == dep) {
+            return Err(format!(
+                "Feature {} is for {}, which is not a dependency of this program",
+                feature, dep
+            )
+            .into());
+        }
+        crate_features.entry(dep).or_default().push(name);
+    }
+    Ok(sorted
+        .iter()
+        .map(|(k, v)| {
+            let features_str = match crate_features.get_mut(k.as_str()) {
+                Some(names) => {
+                    names.sort();
+                    names.dedup();
+                    format!(
+                        ", features = [{}]",
+                        names
+                            .iter()
+                            .map(|n| format!("\"{}\"", n))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )
+                }
+                None => "".to_string(),
+            };
+            let parts = v.split("#").collect::<Vec<&str>>();
+            if parts.len() == 2 {
+                format!(
+                    "{} = {{ git = \"{}\", branch = \"{}\"{} }}",
+                    k, parts[0], parts[1], features_str
+                )
+            } else {
+                // We'll assume there's only one part, since the alternative
+                // wouldn't parse properly. If it blows up, it's on them.
+                format!("{} = {{ git = \"{}\"{} }}", k, v, features_str)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+/// What `to_rs` emits: a binary with a `main` function and a `Cargo.toml` to build it, a plain
+/// module to include in an existing project, or a static library to link into a C program
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum RsEmit {
+    Binary,
+    Module,
+    Staticlib,
+}
+
+/// The `build_staticlib` function builds the Rust generated for a static library in a fresh cargo
+/// project with `crate-type = ["staticlib"]`, then copies the library (`lib<name>.a`, or
+/// `<name>.lib` on Windows) into the current directory.
+fn build_staticlib(
+    name: &str,
+    rs_str: &str,
+    deps_str: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = uncached_dir()?;
+    let res = build_staticlib_in(&dir, name, rs_str, deps_str);
+    if dir.exists() {
+        remove_dir_all(&dir)?;
+    }
+    res
+}
+
+/// The `build_staticlib_in` function does the actual work of `build_staticlib` in the specified
+/// directory.
+fn build_staticlib_in(
+    dir: &Path,
+    name: &str,
+    rs_str: &str,
+    deps_str: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Crate names can't have dashes, and the library file is named for the crate
+    let crate_name = name.replace('-', "_");
+    create_dir_all(dir.join("src"))?;
+    write(dir.join("src").join("lib.rs"), rs_str)?;
+    write(
+        dir.join("Cargo.toml"),
+        format!(
+            "[package]\nname = \"{}\"\nedition = \"2021\"\n\n[lib]\ncrate-type = [\"staticlib\"]\n\n[dependencies]\n{}",
+            crate_name, deps_str
+        ),
+    )?;
+    let o = Command::new("cargo")
+        .current_dir(dir)
+        .arg("build")
+        .arg("--release")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()?;
+    if !o.status.success() {
+        eprintln!("Compilation failed after successful translation to Rust. Likely something is wrong with the bindings.");
+        eprintln!("{}", String::from_utf8_lossy(&o.stdout));
+        eprintln!("{}", String::from_utf8_lossy(&o.stderr));
+        return Err("Rust compilation error".into());
+    }
+    let lib_name = if cfg!(windows) {
+        format!("{}.lib", crate_name)
+    } else {
+        format!("lib{}.a", crate_name)
+    };
+    copy(
+        dir.join("target").join("release").join(&lib_name),
+        current_dir()?.join(&lib_name),
+    )?;
+    Ok(())
+}
+
+/// The module style `to_js` emits, ES modules or CommonJS
+#[derive(Clone, Copy, Debug, PartialEq, clap::ValueEnum)]
+pub enum JsModule {
+    Esm,
+    Cjs,
+}
+
 /// The `to_rs` function is an thin wrapper on top of `lntors` that shoves the output into a `.rs`
-/// file.
-pub fn to_rs(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
+/// file. The `features` are enabled on the dependencies of the `Cargo.toml` emitted for binaries.
+/// A static library exports every function the source file exports, so `entry_fn` is unused, and
+/// it is built right away into a `.a` file in the current directory.
+pub fn to_rs(
+    source_file: String,
+    entry_fn: String,
+    emit: RsEmit,
+    features: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
     Program::set_target_lang_rs();
     let mut program = Program::get_program();
     program
@@ -757,7 +1315,11 @@ pub fn to_rs(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
         .insert("ALAN_TARGET".to_string(), "release".to_string());
     Program::return_program(program);
     // Generate the rust code to compile
-    let (rs_str, deps) = lntors(source_file.clone())?;
+    let (rs_str, deps) = match emit {
+        RsEmit::Binary => lntors(source_file.clone(), entry_fn)?,
+        RsEmit::Module => lntors_module(source_file.clone(), entry_fn)?,
+        RsEmit::Staticlib => lntors_staticlib(source_file.clone())?,
+    };
     // Shove it into a temp file for rustc
     let out_file = match PathBuf::from(source_file.clone()).file_stem() {
         Some(pb) => format!("{}.rs", pb.to_string_lossy()),
@@ -765,30 +1327,26 @@ pub fn to_rs(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
             return Err("Invalid path".into());
         }
     };
-    write(out_file, rs_str)?;
-    if !deps.is_empty() {
+    write(&out_file, &rs_str)?;
+    if emit == RsEmit::Staticlib {
+        return build_staticlib(
+            &PathBuf::from(source_file)
+                .file_stem()
+                .unwrap()
+                .to_string_lossy(),
+            &rs_str,
+            &cargo_deps(deps.iter(), &features)?,
+        );
+    }
+    // A module is built by the project it is included in, so it doesn't get its own `Cargo.toml`
+    if emit == RsEmit::Binary && !deps.is_empty() {
         let cargo_str = format!(
             "[package]\nname = \"{}\"\nedition = \"2021\"\n\n[dependencies]\n{}",
             PathBuf::from(source_file)
                 .file_stem()
                 .unwrap()
                 .to_string_lossy(),
-            deps.iter()
-                .map(|(k, v)| {
-                    let parts = v.split("#").collect::<Vec<&str>>();
-                    if parts.len() == 2 {
-                        format!(
-                            "{} = {{ git = \"{}\", branch = \"{}\" }}",
-                            k, parts[0], parts[1]
-                        )
-                    } else {
-                        // We'll assume there's only one part, since the alternative
-                        // wouldn't parse properly. If it blows up, it's on them.
-                        format!("{} = {{ git = \"{}\" }}", k, v)
-                    }
-                })
-                .collect::<Vec<String>>()
-                .join("\n")
+            cargo_deps(deps.iter(), &features)?
         );
         write("Cargo.toml", cargo_str)?;
     }
@@ -797,7 +1355,11 @@ pub fn to_rs(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
 
 /// The `to_js` function is an thin wrapper on top of `lntojs` that shoves the output into a `.js`
 /// file.
-pub fn to_js(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
+pub fn to_js(
+    source_file: String,
+    entry_fn: String,
+    module: JsModule,
+) -> Result<(), Box<dyn std::error::Error>> {
     Program::set_target_lang_js();
     let mut program = Program::get_program();
     program
@@ -805,7 +1367,8 @@ pub fn to_js(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
         .insert("ALAN_TARGET".to_string(), "release".to_string());
     Program::return_program(program);
     // Generate the rust code to compile
-    let (js_str, deps) = lntojs(source_file.clone())?;
+    let (js_str, map_str, deps) =
+        lntojs_with_sourcemap(source_file.clone(), entry_fn, module == JsModule::Cjs)?;
     // Shove it into a temp file for rustc
     let out_file = match PathBuf::from(source_file.clone()).file_stem() {
         Some(pb) => format!("{}.js", pb.to_string_lossy()),
@@ -813,7 +1376,13 @@ pub fn to_js(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
             return Err("Invalid path".into());
         }
     };
-    write(out_file, js_str)?;
+    // The source map goes alongside the generated file so browsers and node can find it
+    let map_file = format!("{}.map", out_file);
+    write(
+        &out_file,
+        format!("{}\n//# sourceMappingURL={}\n", js_str, map_file),
+    )?;
+    write(map_file, map_str)?;
     if !deps.is_empty() {
         let pkg_str = format!(
             "{{\n  \"name\": \"{}\",\n  \"main\": \"{}.js\",\n  \"dependencies\": {{\n    {}\n  }}\n}}",
@@ -836,3 +1405,366 @@ pub fn to_js(source_file: String) -> Result<(), Box<dyn std::error::Error>> {
     }
     Ok(())
 }
+
+/// The `deps` function runs the translation for the specified target without building anything
+/// and returns the dependencies it found, as the `[dependencies]` section of a `Cargo.toml` file
+/// for Rust or as a JSON object for Javascript.
+pub fn deps(
+    source_file: String,
+    entry_fn: String,
+    target: Target,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match target {
+        Target::Rs => Program::set_target_lang_rs(),
+        Target::Js => Program::set_target_lang_js(),
+    }
+    let mut program = Program::get_program();
+    program
+        .env
+        .insert("ALAN_TARGET".to_string(), "release".to_string());
+    Program::return_program(program);
+    Ok(match target {
+        Target::Rs => {
+            let (_, deps) = lntors(source_file, entry_fn)?;
+            format!("[dependencies]\n{}", cargo_deps(deps.iter(), &[])?)
+        }
+        Target::Js => {
+            let (_, deps) = lntojs(source_file, entry_fn)?;
+            format!(
+                "{{\n{}\n}}",
+                deps.iter()
+                    .map(|(k, v)| format!("  \"{}\": \"{}\"", k, v))
+                    .collect::<Vec<String>>()
+                    .join(",\n")
+            )
+        }
+    })
+}
+
+/// The `check` function loads the specified file (and everything it imports) without generating
+/// any code, returning the warnings found along the way, currently just the unused imports.
+pub fn check(source_file: String) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    Program::set_target_lang_rs();
+    let mut program = Program::get_program();
+    program
+        .env
+        .insert("ALAN_TARGET".to_string(), "release".to_string());
+    Program::return_program(program);
+    Program::load(source_file.clone())?;
+    let program = Program::get_program();
+    let warnings = program.unused_imports(&source_file);
+    Program::return_program(program);
+    warnings
+}
+
+/// The `ast` function loads the specified file (and everything it imports) and renders the types,
+/// constants, and functions of each of the user's files as an indented tree, for debugging the
+/// compiler. Function bodies are shown as the microstatements they were reduced to.
+pub fn ast(source_file: String) -> Result<String, Box<dyn std::error::Error>> {
+    Program::set_target_lang_rs();
+    let mut program = Program::get_program();
+    program
+        .env
+        .insert("ALAN_TARGET".to_string(), "release".to_string());
+    Program::return_program(program);
+    Program::load(source_file.clone())?;
+    let program = Program::get_program();
+    let out = program.imported_files(&source_file).map(|files| {
+        let mut out = String::new();
+        for file in files {
+            if let Some((_, _, scope)) = program.scopes_by_file.get(&file) {
+                scope_tree(scope, &mut out);
+            }
+        }
+        out
+    });
+    Program::return_program(program);
+    out
+}
+
+/// Renders a single scope for `ast`
+fn scope_tree(scope: &Scope, out: &mut String) {
+    out.push_str(&format!("scope {}\n", scope.path));
+    // Exported names are marked with `export`, like in the source
+    let export = |name: &str| match scope.exports.contains_key(name) {
+        true => "export ",
+        false => "",
+    };
+    for (name, t) in scope.types.iter() {
+        out.push_str(&format!(
+            "  {}type {} = {}\n",
+            export(name),
+            name,
+            t.clone().to_string()
+        ));
+    }
+    for (name, c) in scope.consts.iter() {
+        out.push_str(&format!(
+            "  {}const {}{}\n",
+            export(name),
+            name,
+            match &c.typename {
+                Some(t) => format!(": {}", t),
+                None => "".to_string(),
+            }
+        ));
+    }
+    for (name, fs) in scope.functions.iter() {
+        for f in fs {
+            out.push_str(&format!(
+                "  {}fn {} :: {}{}\n",
+                export(name),
+                name,
+                f.typen.clone().to_string(),
+                match &f.kind {
+                    FnKind::Normal => "",
+                    FnKind::Bind(_) | FnKind::ExternalBind(..) => " (binding)",
+                    FnKind::Generic(..)
+                    | FnKind::BoundGeneric(..)
+                    | FnKind::ExternalGeneric(..) => " (generic)",
+                    FnKind::Derived | FnKind::DerivedVariadic => " (derived)",
+                    FnKind::Static => " (static)",
+                    FnKind::External(_) => " (external)",
+                }
+            ));
+            for m in &f.microstatements {
+                microstatement_tree(m, 2, out);
+            }
+        }
+    }
+}
+
+/// Renders a microstatement, and the microstatements within it, for `ast`
+fn microstatement_tree(m: &Microstatement, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    match m {
+        Microstatement::Assignment {
+            mutable,
+            name,
+            value,
+        } => {
+            let keyword = if *mutable { "let" } else { "const" };
+            out.push_str(&format!("{}{} {}\n", indent, keyword, name));
+            microstatement_tree(value, depth + 1, out);
+        }
+        Microstatement::Arg { name, typen, .. } => {
+            out.push_str(&format!(
+                "{}arg {}: {}\n",
+                indent,
+                name,
+                typen.clone().to_string()
+            ));
+        }
+        Microstatement::FnCall { function, args } => {
+            out.push_str(&format!(
+                "{}call {} :: {}\n",
+                indent,
+                function.name,
+                function.typen.clone().to_string()
+            ));
+            for arg in args {
+                microstatement_tree(arg, depth + 1, out);
+            }
+        }
+        Microstatement::Closure { function } => {
+            out.push_str(&format!(
+                "{}closure :: {}\n",
+                indent,
+                function.typen.clone().to_string()
+            ));
+            for m in &function.microstatements {
+                microstatement_tree(m, depth + 1, out);
+            }
+        }
+        Microstatement::VarCall { name, args, .. } => {
+            out.push_str(&format!("{}call {}\n", indent, name));
+            for arg in args {
+                microstatement_tree(arg, depth + 1, out);
+            }
+        }
+        Microstatement::Value {
+            typen,
+            representation,
+        } => {
+            out.push_str(&format!(
+                "{}value {}: {}\n",
+                indent,
+                representation,
+                typen.clone().to_string()
+            ));
+        }
+        Microstatement::Array { typen, vals } => {
+            out.push_str(&format!("{}array {}\n", indent, typen.clone().to_string()));
+            for val in vals {
+                microstatement_tree(val, depth + 1, out);
+            }
+        }
+        Microstatement::Format {
+            typen,
+            literals,
+            values,
+        } => {
+            out.push_str(&format!(
+                "{}format {:?}: {}\n",
+                indent,
+                literals,
+                typen.clone().to_string()
+            ));
+            for value in values {
+                microstatement_tree(value, depth + 1, out);
+            }
+        }
+        Microstatement::Return { value } => {
+            out.push_str(&format!("{}return\n", indent));
+            if let Some(v) = value {
+                microstatement_tree(v, depth + 1, out);
+            }
+        }
+    }
+}
+
+/// The code generation targets the compiler supports
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Rs,
+    Js,
+}
+
+/// The `compile_all` function generates the code for each of the requested targets, in order,
+/// parsing each source file only once along the way.
+pub fn compile_all(
+    source_file: String,
+    targets: Vec<Target>,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut out = Vec::new();
+    for target in targets {
+        match target {
+            Target::Rs => Program::set_target_lang_rs(),
+            Target::Js => Program::set_target_lang_js(),
+        }
+        let mut program = Program::get_program();
+        program
+            .env
+            .insert("ALAN_TARGET".to_string(), "release".to_string());
+        Program::return_program(program);
+        out.push(match target {
+            Target::Rs => lntors(source_file.clone(), "main".to_string())?.0,
+            Target::Js => lntojs(source_file.clone(), "main".to_string())?.0,
+        });
+    }
+    Ok(out)
+}
+
+/// The `compile_str` function generates the code for the specified target from in-memory Alan
+/// source code, the same way the web compiler does, so no files need to be written to disk. The
+/// name is used as the file name of the source, and it must export a `main` function.
+pub fn compile_str(
+    name: &str,
+    src: &str,
+    target: Target,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match target {
+        Target::Rs => Program::set_target_lang_rs(),
+        Target::Js => Program::set_target_lang_js(),
+    }
+    let mut program = Program::get_program();
+    program
+        .env
+        .insert("ALAN_TARGET".to_string(), "release".to_string());
+    Program::return_program(program);
+    Scope::from_src(name, src.to_string())?;
+    Ok(match target {
+        Target::Rs => lntors(name.to_string(), "main".to_string())?.0,
+        Target::Js => lntojs(name.to_string(), "main".to_string())?.0,
+    })
+}
+
+/// How long to wait for the file events to settle down before recompiling, since many editors
+/// write a file in several steps on save.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// The `watch` function runs the provided compilation step once, and then again every time the
+/// source file or any of the files it imports changes, until interrupted. Compilation errors are
+/// printed rather than ending the watch.
+pub fn watch(
+    source_file: String,
+    step: impl Fn() -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (tx, rx) = channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    let mut watched_dirs = HashSet::new();
+    watch_loop(&rx, || {
+        // The program caches every file it has loaded, so it needs to be cleared for the changes
+        // to be picked up
+        for rs in [true, false] {
+            if rs {
+                Program::set_target_lang_rs();
+            } else {
+                Program::set_target_lang_js();
+            }
+            let mut program = Program::get_program();
+            program.scopes_by_file.clear();
+            Program::return_program(program);
+        }
+        let start_time = Instant::now();
+        match step() {
+            Ok(_) => println!(
+                "Rebuilt {} in {:.2}sec",
+                source_file,
+                start_time.elapsed().as_secs_f32()
+            ),
+            Err(e) => eprintln!("{}", e),
+        }
+        // The entry file is always watched, even if it failed to load, plus whatever it imported
+        let program = Program::get_program();
+        let mut files = program.imported_files(&source_file).unwrap_or_default();
+        Program::return_program(program);
+        files.push(source_file.clone());
+        let files = files
+            .iter()
+            .filter_map(|f| canonicalize(f).ok())
+            .collect::<HashSet<PathBuf>>();
+        // Editors often save by replacing the file, so the directories are watched instead
+        for file in &files {
+            if let Some(dir) = file.parent() {
+                if !watched_dirs.contains(dir) {
+                    match watcher.watch(dir, RecursiveMode::NonRecursive) {
+                        Ok(_) => {
+                            watched_dirs.insert(dir.to_path_buf());
+                        }
+                        Err(e) => eprintln!("Could not watch {}: {:?}", dir.to_string_lossy(), e),
+                    }
+                }
+            }
+        }
+        files
+    })
+}
+
+/// The `watch_loop` function drives the recompilation for `watch`. The `rebuild` function is called
+/// once immediately and then again whenever a debounced set of events touches one of the files it
+/// returned. It exits when the event source goes away.
+pub(crate) fn watch_loop(
+    rx: &Receiver<notify::Result<Event>>,
+    mut rebuild: impl FnMut() -> HashSet<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut files = rebuild();
+    while let Ok(event) = rx.recv() {
+        let event = event?;
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+        if !event.paths.iter().any(|p| files.contains(p)) {
+            continue;
+        }
+        // Swallow the rest of the burst of events before recompiling
+        loop {
+            match rx.recv_timeout(WATCH_DEBOUNCE) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        files = rebuild();
+    }
+    Ok(())
+}
@@ -1,4 +1,7 @@
-use crate::compile::{bundle, compile, test, to_js, to_rs};
+use crate::compile::{
+    ast, bundle, check, compile, compile_project, deps, test, to_js, to_rs, watch, JsModule,
+    RsEmit, Target, MANIFEST_FILE,
+};
 use clap::{Parser, Subcommand};
 
 pub mod compile;
@@ -23,16 +26,109 @@ enum Commands {
             default_value = "./index.ln"
         )]
         file: String,
+        #[arg(
+            short,
+            long,
+            help = "The exported zero-argument function to use as the entry point",
+            default_value = "main"
+        )]
+        entry: String,
+        #[arg(
+            short,
+            long,
+            help = "Recompile whenever the source file or its imports change",
+            default_value_t = false
+        )]
+        watch: bool,
+        #[arg(
+            long,
+            help = "Build in a fresh directory instead of the shared build cache. Slower, but hermetic",
+            default_value_t = false
+        )]
+        no_cache: bool,
     },
     #[command(about = "Compile .ln file(s) to an executable")]
     Compile {
         #[arg(
             value_name = "LN_FILE",
-            help = ".ln source file to compile.",
+            help = ".ln source file to compile. Defaults to every entry declared in ./alan.toml if it exists, otherwise ./index.ln"
+        )]
+        file: Option<String>,
+        #[arg(
+            short,
+            long,
+            help = "The exported zero-argument function to use as the entry point",
+            default_value = "main"
+        )]
+        entry: String,
+        #[arg(
+            short,
+            long,
+            help = "Recompile whenever the source file or its imports change",
+            default_value_t = false
+        )]
+        watch: bool,
+        #[arg(
+            short,
+            long,
+            value_name = "TRIPLE",
+            help = "The target triple to cross-compile for, eg `aarch64-unknown-linux-gnu`. Defaults to the host"
+        )]
+        target: Option<String>,
+        #[arg(
+            long,
+            help = "Build in a fresh directory instead of the shared build cache. Slower, but hermetic",
+            default_value_t = false
+        )]
+        no_cache: bool,
+        #[arg(
+            long = "feature",
+            value_name = "CRATE/FEATURE",
+            help = "A feature to enable on a Rust dependency, eg `alan_std/some_feature`. Can be repeated"
+        )]
+        features: Vec<String>,
+    },
+    #[command(about = "Check .ln file(s) for errors and warnings without building")]
+    Check {
+        #[arg(
+            value_name = "LN_FILE",
+            help = ".ln source file to check.",
             default_value = "./index.ln"
         )]
         file: String,
     },
+    #[command(about = "Print the parsed structure of .ln file(s), for debugging the compiler")]
+    Ast {
+        #[arg(
+            value_name = "LN_FILE",
+            help = ".ln source file to print the structure of.",
+            default_value = "./index.ln"
+        )]
+        file: String,
+    },
+    #[command(about = "Print the dependencies the .ln file(s) need without building")]
+    Deps {
+        #[arg(
+            value_name = "LN_FILE",
+            help = ".ln source file to find the dependencies of.",
+            default_value = "./index.ln"
+        )]
+        file: String,
+        #[arg(
+            short,
+            long,
+            help = "The exported zero-argument function to use as the entry point",
+            default_value = "main"
+        )]
+        entry: String,
+        #[arg(
+            short,
+            long,
+            help = "List the Node.js dependencies as JSON, not the Rust dependencies as TOML",
+            default_value_t = false
+        )]
+        js: bool,
+    },
     #[command(about = "Compile .ln file(s) to Rust")]
     ToRs {
         #[arg(
@@ -41,6 +137,33 @@ enum Commands {
             default_value = "./index.ln"
         )]
         file: String,
+        #[arg(
+            short,
+            long,
+            help = "The exported zero-argument function to use as the entry point",
+            default_value = "main"
+        )]
+        entry: String,
+        #[arg(
+            short,
+            long,
+            help = "Recompile whenever the source file or its imports change",
+            default_value_t = false
+        )]
+        watch: bool,
+        #[arg(
+            long,
+            value_enum,
+            help = "Emit a binary with a `main` function and `Cargo.toml`, a plain module, or a static library (`.a`) exporting C functions",
+            default_value = "binary"
+        )]
+        emit: RsEmit,
+        #[arg(
+            long = "feature",
+            value_name = "CRATE/FEATURE",
+            help = "A feature to enable on a Rust dependency, eg `alan_std/some_feature`. Can be repeated"
+        )]
+        features: Vec<String>,
     },
     #[command(about = "Compile .ln file(s) to Javascript")]
     ToJs {
@@ -50,6 +173,27 @@ enum Commands {
             default_value = "./index.ln"
         )]
         file: String,
+        #[arg(
+            short,
+            long,
+            help = "The exported zero-argument function to use as the entry point",
+            default_value = "main"
+        )]
+        entry: String,
+        #[arg(
+            short,
+            long,
+            help = "Recompile whenever the source file or its imports change",
+            default_value_t = false
+        )]
+        watch: bool,
+        #[arg(
+            long,
+            value_enum,
+            help = "Load dependencies as ES modules with `import` or as CommonJS with `require`",
+            default_value = "esm"
+        )]
+        module: JsModule,
     },
     #[command(about = "Test a specified .ln file")]
     Test {
@@ -78,6 +222,19 @@ enum Commands {
     },
 }
 
+/// Runs the compilation step once, or keeps re-running it on file changes if watching
+fn maybe_watch(
+    watching: bool,
+    file: &str,
+    step: impl Fn() -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if watching {
+        watch(file.to_string(), step)
+    } else {
+        step()
+    }
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Cli::parse();
     if args.file.is_some() {
@@ -85,11 +242,83 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         Ok(())
     } else {
         match &args.commands {
-            Some(Commands::Bundle { file }) => Ok(bundle(file.to_string())?),
-            Some(Commands::Compile { file }) => Ok(compile(file.to_string())?),
+            Some(Commands::Bundle {
+                file,
+                entry,
+                watch,
+                no_cache,
+            }) => maybe_watch(*watch, file, || {
+                bundle(file.to_string(), entry.to_string(), *no_cache)
+            }),
+            Some(Commands::Check { file }) => {
+                for warning in check(file.to_string())? {
+                    eprintln!("Warning: {}", warning);
+                }
+                Ok(())
+            }
+            Some(Commands::Compile {
+                file,
+                entry,
+                watch,
+                target,
+                no_cache,
+                features,
+            }) => match file {
+                None if std::path::Path::new(MANIFEST_FILE).exists() => {
+                    if *watch {
+                        return Err(format!(
+                            "Watching is not supported when building from {}, pass the entry file to watch instead",
+                            MANIFEST_FILE
+                        )
+                        .into());
+                    }
+                    compile_project(
+                        MANIFEST_FILE.to_string(),
+                        target.clone(),
+                        *no_cache,
+                        features.clone(),
+                    )
+                }
+                _ => {
+                    let file = file.clone().unwrap_or_else(|| "./index.ln".to_string());
+                    maybe_watch(*watch, &file, || {
+                        compile(
+                            file.clone(),
+                            entry.to_string(),
+                            target.clone(),
+                            *no_cache,
+                            features.clone(),
+                        )
+                    })
+                }
+            },
+            Some(Commands::Ast { file }) => {
+                print!("{}", ast(file.to_string())?);
+                Ok(())
+            }
+            Some(Commands::Deps { file, entry, js }) => {
+                let target = if *js { Target::Js } else { Target::Rs };
+                println!("{}", deps(file.to_string(), entry.to_string(), target)?);
+                Ok(())
+            }
             Some(Commands::Test { file, js }) => Ok(test(file.to_string(), *js)?),
-            Some(Commands::ToRs { file }) => Ok(to_rs(file.to_string())?),
-            Some(Commands::ToJs { file }) => Ok(to_js(file.to_string())?),
+            Some(Commands::ToRs {
+                file,
+                entry,
+                watch,
+                emit,
+                features,
+            }) => maybe_watch(*watch, file, || {
+                to_rs(file.to_string(), entry.to_string(), *emit, features.clone())
+            }),
+            Some(Commands::ToJs {
+                file,
+                entry,
+                watch,
+                module,
+            }) => maybe_watch(*watch, file, || {
+                to_js(file.to_string(), entry.to_string(), *module)
+            }),
             _ => Err("Command not yet supported".into()),
         }
     }
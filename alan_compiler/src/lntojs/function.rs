@@ -3,6 +3,7 @@ use std::sync::Arc;
 use nom::combinator::all_consuming;
 use ordered_hash_map::OrderedHashMap;
 
+use crate::lntojs::sourcemap::ORIGIN_MARKER;
 use crate::lntojs::typen;
 use crate::parse::{booln, integer, real};
 use crate::program::{ArgKind, CType, FnKind, Function, Microstatement, Program, Scope};
@@ -317,6 +318,21 @@ pub fn from_microstatement(
             }
             Ok((format!("[{}]", val_representations.join(", ")), out, deps))
         }
+        Microstatement::Format {
+            literals, values, ..
+        } => {
+            let mut template = String::new();
+            for (i, literal) in literals.iter().enumerate() {
+                template.push_str(&literal.replace('`', "\\`").replace("${", "\\${"));
+                if let Some(value) = values.get(i) {
+                    let (rep, o, d) = from_microstatement(value, scope, parent_fn, out, deps)?;
+                    out = o;
+                    deps = d;
+                    template.push_str(&format!("${{{}.val}}", rep));
+                }
+            }
+            Ok((format!("new alan_std.Str(`{}`)", template), out, deps))
+        }
         Microstatement::FnCall { function, args } => {
             let mut arg_types = Vec::new();
             let mut arg_type_strs = Vec::new();
@@ -1383,9 +1399,14 @@ pub fn generate(
     // a shared library). LLVM *probably* doesn't deduplicate this redundancy, so this will need to
     // be revisited, but it eliminates a whole host of generation problems that I can come back to
     // later.
+    let origin = match function.line {
+        Some(line) => format!("{}{} {}\n", ORIGIN_MARKER, function.origin_scope_path, line),
+        None => "".to_string(),
+    };
     fn_string = format!(
-        "{}async function {}({}) {{\n",
+        "{}{}async function {}({}) {{\n",
         fn_string,
+        origin,
         jsname.clone(),
         arg_strs.join(", "),
     )
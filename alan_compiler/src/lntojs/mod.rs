@@ -4,49 +4,64 @@ use crate::lntojs::function::generate as fn_generate;
 use crate::program::{CType, Program};
 
 mod function;
+mod sourcemap;
 mod typen;
 
 pub fn lntojs(
     entry_file: String,
+    entry_fn: String,
 ) -> Result<(String, OrderedHashMap<String, String>), Box<dyn std::error::Error>> {
+    let (js, _, deps) = lntojs_with_sourcemap(entry_file, entry_fn, false)?;
+    Ok((js, deps))
+}
+
+/// The generated Javascript, its source map, and the npm dependencies it needs
+pub type GeneratedJs = (String, String, OrderedHashMap<String, String>);
+
+/// Generates the Javascript for the entry function along with a (coarse, function-level) source
+/// map pointing back to the Alan source files the generated functions came from. The dependencies
+/// are loaded with `import` unless `commonjs` is set, in which case `require` is used instead.
+pub fn lntojs_with_sourcemap(
+    entry_file: String,
+    entry_fn: String,
+    commonjs: bool,
+) -> Result<GeneratedJs, Box<dyn std::error::Error>> {
     Program::set_target_lang_js();
     Program::load(entry_file.clone())?;
     let program = Program::get_program();
+    // The program is handed back even if generation fails, so a long-running process (like
+    // `--watch`) still has it for the next build
+    let res = generate(&program, entry_file, entry_fn, commonjs);
+    Program::return_program(program);
+    res
+}
+
+fn generate(
+    program: &Program,
+    entry_file: String,
+    entry_fn: String,
+    commonjs: bool,
+) -> Result<GeneratedJs, Box<dyn std::error::Error>> {
     let scope = program.scope_by_file(&entry_file)?;
-    // Without support for building shared libs yet, assume there is an `export fn main` in the
-    // entry file or fail otherwise
-    match scope.exports.get("main") {
-        Some(_) => {}
-        None => {
-            return Err(
-                "Entry file has no `main` function exported. This is not yet supported.".into(),
-            );
-        }
-    };
-    // Getting here *should* guarantee that the `main` function exists, so let's grab it.
-    let func = match scope.functions.get("main") {
-        Some(f) => f,
-        None => {
-            return Err(
-                "An export has been found without a definition. This should be impossible.".into(),
-            );
-        }
-    };
-    // The `main` function takes no arguments, for now. It could have a return type, but we don't
-    // support that, yet. Also assert that there is only a single `main` function, since *usually*
-    // you're allowed to have multiple functions with the same name as long as they have different
-    // arguments.
-    assert_eq!(func.len(), 1);
-    assert_eq!(func[0].args().len(), 0);
-    // Assertion proven, start emitting the `main` function to run as an IIFE
+    let test_mode = program.env.get("ALAN_TARGET").is_some_and(|t| t == "test");
+    if let Some((fns, deps)) = scope.test_harness(&entry_fn, test_mode, fn_generate, test_main)? {
+        let (js, map) = sourcemap::generate(format!(
+            "{}\n{}\nmain();",
+            import_header(&deps, commonjs),
+            fns.into_values().collect::<Vec<String>>().join("\n"),
+        ));
+        return Ok((js, map, deps));
+    }
+    let func = scope.entry_function(&entry_file, &entry_fn)?;
+    // Checks passed, start emitting the entry function as `main` to run as an IIFE
     let (fns, deps) = fn_generate(
         "main".to_string(),
-        &func[0],
+        func,
         scope,
         OrderedHashMap::new(),
         OrderedHashMap::new(),
     )?;
-    let main_call = if let CType::Type(n, _) = &*func[0].rettype() {
+    let main_call = if let CType::Type(n, _) = &*func.rettype() {
         if n == "ExitCode" {
             "main().then(process.exit);"
         } else {
@@ -55,17 +70,54 @@ pub fn lntojs(
     } else {
         "main();"
     };
-    Program::return_program(program);
-    Ok((
-        format!(
-            "{}\n{}\n{}",
-            deps.keys()
-                .map(|k| format!("import * as {} from \"{}\";", k, k))
-                .collect::<Vec<String>>()
-                .join("\n"),
-            fns.into_values().collect::<Vec<String>>().join("\n"),
-            main_call,
-        ),
-        deps,
-    ))
+    let (js, map) = sourcemap::generate(format!(
+        "{}\n{}\n{}",
+        import_header(&deps, commonjs),
+        fns.into_values().collect::<Vec<String>>().join("\n"),
+        main_call,
+    ));
+    Ok((js, map, deps))
+}
+
+/// Loads each of the dependencies with `import`, or with `require` if `commonjs` is set
+fn import_header(deps: &OrderedHashMap<String, String>, commonjs: bool) -> String {
+    deps.keys()
+        .map(|k| {
+            if commonjs {
+                format!("const {} = require(\"{}\");", k, k)
+            } else {
+                format!("import * as {} from \"{}\";", k, k)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Generates the `main` function that runs each of the test functions and reports how many passed
+/// and which failed. It exits with an error code if any of them failed.
+fn test_main(names: &[&str]) -> String {
+    format!(
+        r#"async function main() {{
+  const tests = [{}];
+  let failed = [];
+  for (const [name, test] of tests) {{
+    if ((await test()).val) {{
+      console.log(`✅ ${{name}}`);
+    }} else {{
+      console.log(`❎ ${{name}}`);
+      failed.push(name);
+    }}
+  }}
+  console.log(`${{tests.length - failed.length}} passed, ${{failed.length}} failed`);
+  if (failed.length > 0) {{
+    console.error(`Failed tests: ${{failed.join(', ')}}`);
+    process.exit(1);
+  }}
+}}"#,
+        names
+            .iter()
+            .map(|name| format!("[\"{0}\", {0}]", name))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
 }
@@ -0,0 +1,83 @@
+/// Every generated function defined in Alan source starts with a comment line naming the Alan file
+/// and line it was generated from. These lines are removed from the final output and turned into the source map.
+pub const ORIGIN_MARKER: &str = "//# alanOrigin ";
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes a number in the base64 VLQ format used by the `mappings` field of a source map
+fn vlq(n: i64) -> String {
+    let mut v = if n < 0 { ((-n) << 1) | 1 } else { n << 1 };
+    let mut out = String::new();
+    loop {
+        let mut digit = v & 0b11111;
+        v >>= 5;
+        if v > 0 {
+            digit |= 0b100000;
+        }
+        out.push(BASE64[digit as usize] as char);
+        if v == 0 {
+            break;
+        }
+    }
+    out
+}
+
+/// Strips the origin markers out of the generated Javascript and produces a (version 3) source
+/// map for it. The mapping is coarse: every line of a generated function points at the line where
+/// the Alan function it came from is defined. Functions from the standard library are left
+/// unmapped.
+pub fn generate(js: String) -> (String, String) {
+    let mut out_lines = Vec::new();
+    let mut sources: Vec<String> = Vec::new();
+    let mut mappings = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    // Source map segments are relative to the previous segment, so track the prior values
+    let mut prior_source = 0;
+    let mut prior_line = 0;
+    for line in js.lines() {
+        if let Some(origin) = line.strip_prefix(ORIGIN_MARKER) {
+            current = origin.rsplit_once(' ').and_then(|(path, line)| {
+                if path.starts_with('@') {
+                    return None;
+                }
+                let def_line = line.parse::<usize>().ok()?;
+                let source_idx = match sources.iter().position(|s| s == path) {
+                    Some(i) => i,
+                    None => {
+                        sources.push(path.to_string());
+                        sources.len() - 1
+                    }
+                };
+                Some((source_idx, def_line - 1))
+            });
+            continue;
+        }
+        out_lines.push(line);
+        match current {
+            Some((source_idx, def_line)) => {
+                mappings.push(format!(
+                    "A{}{}A",
+                    vlq(source_idx as i64 - prior_source as i64),
+                    vlq(def_line as i64 - prior_line as i64),
+                ));
+                prior_source = source_idx;
+                prior_line = def_line;
+            }
+            None => mappings.push("".to_string()),
+        }
+        // Generated functions always end with a closing brace on its own line
+        if line == "}" {
+            current = None;
+        }
+    }
+    let map = format!(
+        "{{\"version\":3,\"sources\":[{}],\"names\":[],\"mappings\":\"{}\"}}",
+        sources
+            .iter()
+            .map(|s| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect::<Vec<String>>()
+            .join(","),
+        mappings.join(";"),
+    );
+    (out_lines.join("\n"), map)
+}
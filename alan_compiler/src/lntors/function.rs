@@ -4,9 +4,17 @@ use std::sync::Arc;
 
 use ordered_hash_map::OrderedHashMap;
 
+use crate::lntors::add_dep;
 use crate::lntors::typen;
 use crate::program::{ArgKind, CType, FnKind, Function, Microstatement, Program, Scope};
 
+fn is_number_rtype(rusttype: &str) -> bool {
+    matches!(
+        rusttype,
+        "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" | "f32" | "f64"
+    )
+}
+
 pub fn from_microstatement(
     microstatement: &Microstatement,
     parent_fn: &Function,
@@ -130,7 +138,7 @@ pub fn from_microstatement(
                                         CType::TString(s) => s.clone(),
                                         _ => CType::fail("Dependency versions must be strings"),
                                     };
-                                    deps.insert(name, version);
+                                    add_dep(&mut deps, name, version)?;
                                 }
                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                             }
@@ -146,7 +154,7 @@ pub fn from_microstatement(
                                     CType::TString(s) => s.clone(),
                                     _ => CType::fail("Dependency versions must be strings"),
                                 };
-                                deps.insert(name, version);
+                                add_dep(&mut deps, name, version)?;
                             }
                             _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                         }
@@ -232,7 +240,7 @@ pub fn from_microstatement(
                                                         CType::TString(s) => s.clone(),
                                                         _ => CType::fail("Dependency versions must be strings"),
                                                     };
-                                                    deps.insert(name, version);
+                                                    add_dep(&mut deps, name, version)?;
                                                 }
                                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                             }
@@ -248,7 +256,7 @@ pub fn from_microstatement(
                                                     CType::TString(s) => s.clone(),
                                                     _ => CType::fail("Dependency versions must be strings"),
                                                 };
-                                                deps.insert(name, version);
+                                                add_dep(&mut deps, name, version)?;
                                             }
                                             _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                         }
@@ -276,7 +284,7 @@ pub fn from_microstatement(
                                                         CType::TString(s) => s.clone(),
                                                         _ => CType::fail("Dependency versions must be strings"),
                                                     };
-                                                    deps.insert(name, version);
+                                                    add_dep(&mut deps, name, version)?;
                                                 }
                                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                             }
@@ -292,7 +300,7 @@ pub fn from_microstatement(
                                                     CType::TString(s) => s.clone(),
                                                     _ => CType::fail("Dependency versions must be strings"),
                                                 };
-                                                deps.insert(name, version);
+                                                add_dep(&mut deps, name, version)?;
                                             }
                                             _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                         }
@@ -307,13 +315,46 @@ pub fn from_microstatement(
             }
             _ => Ok((representation.clone(), out, deps)),
         },
-        Microstatement::Array { vals, .. } => {
+        Microstatement::Array { typen, vals } => {
             let mut val_representations = Vec::new();
             for val in vals {
                 let (rep, o, d) = from_microstatement(val, parent_fn, scope, out, deps)?;
-                val_representations.push(rep);
                 out = o;
                 deps = d;
+                // A variable placed in an array is cloned, as it may still be used afterwards,
+                // unless it is a number or boolean that is simply copied
+                let is_variable = matches!(val, Microstatement::Value { .. })
+                    && rep != "true"
+                    && rep != "false"
+                    && rep.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && rep.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if is_variable {
+                    let (rusttype, d) = typen::ctype_to_rtype(val.get_type(), false, deps)?;
+                    deps = d;
+                    if !is_number_rtype(&rusttype) && rusttype != "bool" {
+                        val_representations.push(format!("{}.clone()", rep));
+                        continue;
+                    }
+                }
+                val_representations.push(rep);
+            }
+            // Rust falls back to `i32` and `f64` for unconstrained number literals, which silently
+            // changes behavior for generic bindings like `hash`, so number arrays are typed
+            // explicitly
+            if let CType::Array(t) = &**typen {
+                let (rusttype, d) = typen::ctype_to_rtype(t.clone(), false, deps)?;
+                deps = d;
+                if is_number_rtype(&rusttype) {
+                    return Ok((
+                        format!(
+                            "Vec::<{}>::from([{}])",
+                            rusttype,
+                            val_representations.join(", ")
+                        ),
+                        out,
+                        deps,
+                    ));
+                }
             }
             Ok((
                 format!("vec![{}]", val_representations.join(", ")),
@@ -321,6 +362,27 @@ pub fn from_microstatement(
                 deps,
             ))
         }
+        Microstatement::Format {
+            literals, values, ..
+        } => {
+            let mut args = Vec::new();
+            for value in values {
+                let (rep, o, d) = from_microstatement(value, parent_fn, scope, out, deps)?;
+                out = o;
+                deps = d;
+                args.push(rep);
+            }
+            let template = literals
+                .iter()
+                .map(|l| l.replace('{', "{{").replace('}', "}}"))
+                .collect::<Vec<String>>()
+                .join("{}");
+            Ok((
+                format!("format!(\"{}\", {})", template, args.join(", ")),
+                out,
+                deps,
+            ))
+        }
         Microstatement::FnCall { function, args } => {
             // Hackery to inline `if` calls *if* it's safe to do so.
             if let FnKind::Bind(fname) = &function.kind {
@@ -439,7 +501,7 @@ pub fn from_microstatement(
                                             CType::TString(s) => s.clone(),
                                             _ => CType::fail("Dependency versions must be strings"),
                                         };
-                                        deps.insert(name, version);
+                                        add_dep(&mut deps, name, version)?;
                                     }
                                     _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                 }
@@ -455,7 +517,7 @@ pub fn from_microstatement(
                                         CType::TString(s) => s.clone(),
                                         _ => CType::fail("Dependency versions must be strings"),
                                     };
-                                    deps.insert(name, version);
+                                    add_dep(&mut deps, name, version)?;
                                 }
                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                             }
@@ -512,7 +574,7 @@ pub fn from_microstatement(
                                             CType::TString(s) => s.clone(),
                                             _ => CType::fail("Dependency versions must be strings"),
                                         };
-                                        deps.insert(name, version);
+                                        add_dep(&mut deps, name, version)?;
                                     }
                                     _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                 }
@@ -528,7 +590,7 @@ pub fn from_microstatement(
                                         CType::TString(s) => s.clone(),
                                         _ => CType::fail("Dependency versions must be strings"),
                                     };
-                                    deps.insert(name, version);
+                                    add_dep(&mut deps, name, version)?;
                                 }
                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                             }
@@ -579,7 +641,7 @@ pub fn from_microstatement(
                                                         CType::TString(s) => s.clone(),
                                                         _ => CType::fail("Dependency versions must be strings"),
                                                     };
-                                                    deps.insert(name, version);
+                                                    add_dep(&mut deps, name, version)?;
                                                 }
                                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                             }
@@ -595,7 +657,7 @@ pub fn from_microstatement(
                                                     CType::TString(s) => s.clone(),
                                                     _ => CType::fail("Dependency versions must be strings"),
                                                 };
-                                                deps.insert(name, version);
+                                                add_dep(&mut deps, name, version)?;
                                             }
                                             _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                         }
@@ -1060,44 +1122,47 @@ pub fn from_microstatement(
                             };
                         }
                         match &*inner_ret_type {
-                            CType::Buffer(_, s) => {
+                            CType::Buffer(t, s) => {
                                 let size = match **s {
                                     CType::Int(s) => Ok(s as usize),
                                     _ => Err("Somehow received a buffer with a non-integer size"
                                         .to_string()),
                                 }?;
-                                if argstrs.len() == size {
-                                    return Ok((
-                                        format!(
-                                            "[{}]",
-                                            argstrs
-                                                .iter()
-                                                .map(|a| match a.strip_prefix("&mut ") {
-                                                    Some(v) => v,
-                                                    None => a,
-                                                })
-                                                .collect::<Vec<&str>>()
-                                                .join(", ")
-                                        ),
-                                        out,
-                                        deps,
-                                    ));
+                                let literal = if argstrs.len() == size {
+                                    format!(
+                                        "[{}]",
+                                        argstrs
+                                            .iter()
+                                            .map(|a| match a.strip_prefix("&mut ") {
+                                                Some(v) => v,
+                                                None => a,
+                                            })
+                                            .collect::<Vec<&str>>()
+                                            .join(", ")
+                                    )
                                 } else if argstrs.len() == 1 {
+                                    format!(
+                                        "[{};{}]",
+                                        match argstrs[0].strip_prefix("&mut ") {
+                                            Some(v) => v,
+                                            None => &argstrs[0],
+                                        },
+                                        size
+                                    )
+                                } else {
+                                    return Err(format!("Invalid arguments {} provided for Buffer constructor function, must be either 1 element to fill, or the full size of the buffer", argstrs.join(", ")).into());
+                                };
+                                // Same as array literals, number buffers are typed explicitly
+                                let (rusttype, d) = typen::ctype_to_rtype(t.clone(), false, deps)?;
+                                deps = d;
+                                if is_number_rtype(&rusttype) {
                                     return Ok((
-                                        format!(
-                                            "[{};{}]",
-                                            match argstrs[0].strip_prefix("&mut ") {
-                                                Some(v) => v,
-                                                None => &argstrs[0],
-                                            },
-                                            size
-                                        ),
+                                        format!("<[{}; {}]>::from({})", rusttype, size, literal),
                                         out,
                                         deps,
                                     ));
-                                } else {
-                                    return Err(format!("Invalid arguments {} provided for Buffer constructor function, must be either 1 element to fill, or the full size of the buffer", argstrs.join(", ")).into());
                                 }
+                                return Ok((literal, out, deps));
                             }
                             CType::Array(_) => {
                                 return Ok((
@@ -1415,7 +1480,7 @@ pub fn from_microstatement(
                                                                         CType::TString(s) => s.clone(),
                                                                         _ => CType::fail("Dependency versions must be strings"),
                                                                     };
-                                                                    deps.insert(name, version);
+                                                                    add_dep(&mut deps, name, version)?;
                                                                 }
                                                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                                             }
@@ -1431,7 +1496,7 @@ pub fn from_microstatement(
                                                                     CType::TString(s) => s.clone(),
                                                                     _ => CType::fail("Dependency versions must be strings"),
                                                                 };
-                                                                deps.insert(name, version);
+                                                                add_dep(&mut deps, name, version)?;
                                                             }
                                                             _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                                         }
@@ -1,52 +1,231 @@
+use std::sync::Arc;
+
 use ordered_hash_map::OrderedHashMap;
 
 use crate::lntors::function::generate as fn_generate;
-use crate::program::Program;
+use crate::lntors::typen::generate as type_generate;
+use crate::program::{ArgKind, CType, Export, FnKind, Function, Program};
 
 mod function;
 mod typen;
+mod wgsl;
 
 pub fn lntors(
     entry_file: String,
+    entry_fn: String,
+) -> Result<(String, OrderedHashMap<String, String>), Box<dyn std::error::Error>> {
+    generate(entry_file, entry_fn, false)
+}
+
+/// Generates the Rust for the entry function as a plain module to include in an existing project,
+/// so the entry function keeps its own name and is made public rather than becoming `main`.
+pub fn lntors_module(
+    entry_file: String,
+    entry_fn: String,
+) -> Result<(String, OrderedHashMap<String, String>), Box<dyn std::error::Error>> {
+    generate(entry_file, entry_fn, true)
+}
+
+/// Records a Rust dependency of the generated code. A crate can only be listed once in the
+/// generated `Cargo.toml`, so the same crate requested with a different version is an error.
+fn add_dep(
+    deps: &mut OrderedHashMap<String, String>,
+    name: String,
+    version: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match deps.get(&name) {
+        Some(prior) if *prior != version => Err(format!(
+            "Dependency {} has conflicting versions {} and {}",
+            name, prior, version
+        )
+        .into()),
+        _ => {
+            deps.insert(name, version);
+            Ok(())
+        }
+    }
+}
+
+/// The Rust types that can be passed across the C ABI as-is
+const FFI_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "f32", "f64", "bool",
+];
+
+/// Generates the Rust for a static library to link into a C (or Rust) program. Every function the
+/// entry file exports is generated under an internal name and wrapped in a `#[no_mangle] extern
+/// "C"` function with the exported name, so unlike a binary the exported functions may take
+/// arguments. Their arguments and return types must be numbers or booleans, which can cross the
+/// C ABI unchanged.
+pub fn lntors_staticlib(
+    entry_file: String,
 ) -> Result<(String, OrderedHashMap<String, String>), Box<dyn std::error::Error>> {
     Program::set_target_lang_rs();
     Program::load(entry_file.clone())?;
     let program = Program::get_program();
+    // Like `generate`, the program is handed back even if generation fails
+    let res = staticlib_from(&program, entry_file);
+    Program::return_program(program);
+    res
+}
+
+fn staticlib_from(
+    program: &Program,
+    entry_file: String,
+) -> Result<(String, OrderedHashMap<String, String>), Box<dyn std::error::Error>> {
     let scope = program.scope_by_file(&entry_file)?;
-    // Without support for building shared libs yet, assume there is an `export fn main` in the
-    // entry file or fail otherwise
-    match scope.exports.get("main") {
-        Some(_) => {}
-        None => {
-            return Err(
-                "Entry file has no `main` function exported. This is not yet supported.".into(),
-            );
+    wgsl::validate(program)?;
+    let mut fns = OrderedHashMap::new();
+    let mut deps = OrderedHashMap::new();
+    let mut wrappers = Vec::new();
+    for (name, export) in scope.exports.iter() {
+        if !matches!(export, Export::Function) {
+            continue;
         }
-    };
-    // Getting here *should* guarantee that the `main` function exists, so let's grab it.
-    let func = match scope.functions.get("main") {
-        Some(f) => f,
-        None => {
-            return Err(
-                "An export has been found without a definition. This should be impossible.".into(),
-            );
+        // Exported types also export their constructors and accessors, and generic functions have
+        // no single signature to give C, so only plain functions are included in the library
+        let funcs = match scope.functions.get(name) {
+            Some(fs) => fs
+                .iter()
+                .filter(|f| matches!(f.kind, FnKind::Normal))
+                .collect::<Vec<&Arc<Function>>>(),
+            None => continue,
+        };
+        let func = match funcs.as_slice() {
+            [] => continue,
+            [f] => f,
+            _ => {
+                return Err(format!(
+                    "Exported function `{}` has {} definitions, but C functions can't be overloaded",
+                    name,
+                    funcs.len()
+                )
+                .into());
+            }
+        };
+        let rustname = format!("alan_{}", name);
+        (fns, deps) = fn_generate(rustname.clone(), func, scope, fns, deps)?;
+        let mut arg_strs = Vec::new();
+        let mut call_strs = Vec::new();
+        for (l, k, t) in func.args() {
+            let (t_str, o, d) = type_generate(t.clone(), fns, deps)?;
+            fns = o;
+            deps = d;
+            if matches!(k, ArgKind::Mut) || !FFI_TYPES.contains(&t_str.as_str()) {
+                return Err(format!(
+                    "Exported function `{}` can't be included in a static library because its argument `{}` has type `{}`. Only numbers and booleans can be passed to and from C",
+                    name,
+                    l,
+                    t.to_string()
+                )
+                .into());
+            }
+            arg_strs.push(format!("{}: {}", l, t_str));
+            call_strs.push(match k {
+                ArgKind::Own => l,
+                _ => format!("&{}", l),
+            });
         }
+        let ret = func.rettype().degroup();
+        let ret_str = match &*ret {
+            CType::Void => "".to_string(),
+            CType::Type(n, _) if n == "void" => "".to_string(),
+            _ => {
+                let (t_str, o, d) = type_generate(ret.clone(), fns, deps)?;
+                fns = o;
+                deps = d;
+                if !FFI_TYPES.contains(&t_str.as_str()) {
+                    return Err(format!(
+                        "Exported function `{}` can't be included in a static library because it returns `{}`. Only numbers and booleans can be passed to and from C",
+                        name,
+                        ret.clone().to_string()
+                    )
+                    .into());
+                }
+                format!(" -> {}", t_str)
+            }
+        };
+        wrappers.push(format!(
+            "#[no_mangle]\npub extern \"C\" fn {}({}){} {{\n    {}({})\n}}\n",
+            name,
+            arg_strs.join(", "),
+            ret_str,
+            rustname,
+            call_strs.join(", ")
+        ));
+    }
+    if wrappers.is_empty() {
+        return Err(format!(
+            "Entry file `{}` does not export any functions to include in a static library",
+            entry_file
+        )
+        .into());
+    }
+    Ok((
+        format!(
+            "use std::io::Write;\n\n{}\n{}",
+            fns.into_values().collect::<Vec<String>>().join("\n"),
+            wrappers.join("\n")
+        ),
+        deps,
+    ))
+}
+
+fn generate(
+    entry_file: String,
+    entry_fn: String,
+    module: bool,
+) -> Result<(String, OrderedHashMap<String, String>), Box<dyn std::error::Error>> {
+    Program::set_target_lang_rs();
+    Program::load(entry_file.clone())?;
+    let program = Program::get_program();
+    // The program is handed back even if generation fails, so a long-running process (like
+    // `--watch`) still has it for the next build
+    let res = generate_from(&program, entry_file, entry_fn, module);
+    Program::return_program(program);
+    res
+}
+
+fn generate_from(
+    program: &Program,
+    entry_file: String,
+    entry_fn: String,
+    module: bool,
+) -> Result<(String, OrderedHashMap<String, String>), Box<dyn std::error::Error>> {
+    let scope = program.scope_by_file(&entry_file)?;
+    // A module has no `main` function, so it never gets the test harness
+    let test_mode = !module && program.env.get("ALAN_TARGET").is_some_and(|t| t == "test");
+    if let Some((fns, deps)) = scope.test_harness(&entry_fn, test_mode, fn_generate, test_main)? {
+        wgsl::validate(program)?;
+        return Ok((
+            format!(
+                "use std::io::Write;\n\n{}",
+                fns.into_values().collect::<Vec<String>>().join("\n")
+            ),
+            deps,
+        ));
+    }
+    let func = scope.entry_function(&entry_file, &entry_fn)?;
+    // Catch any invalid shaders embedded in the source before generating anything
+    wgsl::validate(program)?;
+    // Checks passed, start emitting the entry function as the Rust `main` function, or as a
+    // public function with its own name for a module
+    let rustname = if module {
+        entry_fn.clone()
+    } else {
+        "main".to_string()
     };
-    // The `main` function takes no arguments, for now. It could have a return type, but we don't
-    // support that, yet. Also assert that there is only a single `main` function, since *usually*
-    // you're allowed to have multiple functions with the same name as long as they have different
-    // arguments.
-    assert_eq!(func.len(), 1);
-    assert_eq!(func[0].args().len(), 0);
-    // Assertion proven, start emitting the Rust `main` function
-    let (fns, deps) = fn_generate(
-        "main".to_string(),
-        &func[0],
+    let (mut fns, deps) = fn_generate(
+        rustname.clone(),
+        func,
         scope,
         OrderedHashMap::new(),
         OrderedHashMap::new(),
     )?;
-    Program::return_program(program);
+    if module {
+        if let Some(f) = fns.get_mut(&rustname) {
+            *f = format!("pub {}", f);
+        }
+    }
     // To make `stdout` and `stderr` work properly, we have to `use std::io::Write;` to cause a
     // side-effect to attach a `flush` method to them internally. This is an ugly hack but I can't
     // figure out a better way to accomplish this.
@@ -58,3 +237,34 @@ pub fn lntors(
         deps,
     ))
 }
+
+/// Generates the `main` function that runs each of the test functions and reports how many passed
+/// and which failed. It exits with an error code if any of them failed.
+fn test_main(names: &[&str]) -> String {
+    format!(
+        r#"fn main() {{
+    let tests: [(&str, fn() -> bool); {}] = [{}];
+    let mut failed = Vec::new();
+    for (name, test) in tests {{
+        if test() {{
+            println!("✅ {{}}", name);
+        }} else {{
+            println!("❎ {{}}", name);
+            failed.push(name);
+        }}
+    }}
+    println!("{{}} passed, {{}} failed", tests.len() - failed.len(), failed.len());
+    if !failed.is_empty() {{
+        eprintln!("Failed tests: {{}}", failed.join(", "));
+        std::process::exit(1);
+    }}
+}}
+"#,
+        names.len(),
+        names
+            .iter()
+            .map(|name| format!("(\"{0}\", {0})", name))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
@@ -3,8 +3,104 @@ use std::sync::Arc;
 
 use ordered_hash_map::OrderedHashMap;
 
+use crate::lntors::add_dep;
 use crate::program::CType;
 
+/// Rust types that are known to implement `Debug`. Types bound from elsewhere may not, so a
+/// generated type only derives `Debug` when everything inside of it is in this list
+const DEBUG_TYPES: &[&str] = &[
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "f32",
+    "f64",
+    "bool",
+    "String",
+    "Vec",
+    "Option",
+    "Result",
+    "std::time::Instant",
+    "std::time::Duration",
+    "std::process::ExitCode",
+    "std::collections::HashSet",
+    "alan_std::AlanError",
+    "alan_std::BufferUsages",
+    "alan_std::ExecResult",
+    "alan_std::LruCache",
+    "alan_std::Memoized",
+    "alan_std::OrderedHashMap",
+    "alan_std::RngState",
+    "alan_std::Uuid",
+];
+
+/// Rust types that are known to implement `PartialEq`, used the same way as `DEBUG_TYPES`
+const PARTIAL_EQ_TYPES: &[&str] = &[
+    "i8",
+    "i16",
+    "i32",
+    "i64",
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "f32",
+    "f64",
+    "bool",
+    "String",
+    "Vec",
+    "Option",
+    "std::time::Instant",
+    "std::time::Duration",
+    "std::process::ExitCode",
+    "alan_std::BufferUsages",
+    "alan_std::Uuid",
+];
+
+/// Determines if the given type is built entirely out of the known types, so a trait they all
+/// implement can be derived for it
+fn derivable(ctype: &CType, known: &[&str]) -> bool {
+    match ctype {
+        CType::Void | CType::Int(_) | CType::Float(_) | CType::Bool(_) | CType::TString(_) => true,
+        CType::Binds(n, ts) => {
+            let name = match &**n {
+                CType::TString(s) => s,
+                CType::Import(n, _) => match &**n {
+                    CType::TString(s) => s,
+                    _ => return false,
+                },
+                _ => return false,
+            };
+            known.contains(&name.as_str()) && ts.iter().all(|t| derivable(t, known))
+        }
+        CType::Type(_, t)
+        | CType::Field(_, t)
+        | CType::Group(t)
+        | CType::Mut(t)
+        | CType::Array(t)
+        | CType::Buffer(t, _) => derivable(t, known),
+        CType::Tuple(ts) | CType::Either(ts) => ts.iter().all(|t| derivable(t, known)),
+        _ => false,
+    }
+}
+
+/// Generates the derive attribute for a generated type. `Clone` is always derived, while `Debug`
+/// and `PartialEq` are only derived when all of the inner types support them
+fn derive_attr(ts: &[Arc<CType>]) -> String {
+    let mut derives = vec!["Clone"];
+    if ts.iter().all(|t| derivable(t, DEBUG_TYPES)) {
+        derives.push("Debug");
+    }
+    if ts.iter().all(|t| derivable(t, PARTIAL_EQ_TYPES)) {
+        derives.push("PartialEq");
+    }
+    format!("#[derive({})]", derives.join(", "))
+}
+
 pub fn ctype_to_rtype(
     ctype: Arc<CType>,
     in_function_type: bool,
@@ -111,7 +207,8 @@ pub fn ctype_to_rtype(
                 }
                 let name = t.clone().to_callable_string();
                 Ok((format!(
-                    "#[derive(Clone)]\nenum {} {{ {} }}",
+                    "{}\nenum {} {{ {} }}",
+                    derive_attr(ts),
                     name,
                     enum_type_strs.join(", ")
                 ), deps))
@@ -170,7 +267,7 @@ pub fn ctype_to_rtype(
                                             CType::TString(s) => s.clone(),
                                             _ => CType::fail("Dependency versions must be strings"),
                                         };
-                                        deps.insert(name, version);
+                                        add_dep(&mut deps, name, version)?;
                                     }
                                     _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                 }
@@ -186,7 +283,7 @@ pub fn ctype_to_rtype(
                                         CType::TString(s) => s.clone(),
                                         _ => CType::fail("Dependency versions must be strings"),
                                     };
-                                    deps.insert(name, version);
+                                    add_dep(&mut deps, name, version)?;
                                 }
                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                             }
@@ -243,7 +340,7 @@ pub fn ctype_to_rtype(
                                         CType::TString(s) => s.clone(),
                                         _ => CType::fail("Dependency versions must be strings"),
                                     };
-                                    deps.insert(name, version);
+                                    add_dep(&mut deps, name, version)?;
                                 }
                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                             }
@@ -259,7 +356,7 @@ pub fn ctype_to_rtype(
                                     CType::TString(s) => s.clone(),
                                     _ => CType::fail("Dependency versions must be strings"),
                                 };
-                                deps.insert(name, version);
+                                add_dep(&mut deps, name, version)?;
                             }
                             _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                         }
@@ -394,7 +491,7 @@ pub fn ctype_to_rtype(
                                                     CType::TString(s) => s.clone(),
                                                     _ => CType::fail("Dependency versions must be strings"),
                                                 };
-                                                deps.insert(name, version);
+                                                add_dep(&mut deps, name, version)?;
                                             }
                                             _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                         }
@@ -410,7 +507,7 @@ pub fn ctype_to_rtype(
                                                 CType::TString(s) => s.clone(),
                                                 _ => CType::fail("Dependency versions must be strings"),
                                             };
-                                            deps.insert(name, version);
+                                            add_dep(&mut deps, name, version)?;
                                         }
                                         _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                     }
@@ -441,7 +538,7 @@ pub fn ctype_to_rtype(
                                                         CType::TString(s) => s.clone(),
                                                         _ => CType::fail("Dependency versions must be strings"),
                                                     };
-                                                    deps.insert(name, version);
+                                                    add_dep(&mut deps, name, version)?;
                                                 }
                                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                             }
@@ -457,7 +554,7 @@ pub fn ctype_to_rtype(
                                                     CType::TString(s) => s.clone(),
                                                     _ => CType::fail("Dependency versions must be strings"),
                                                 };
-                                                deps.insert(name, version);
+                                                add_dep(&mut deps, name, version)?;
                                             }
                                             _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                                         }
@@ -555,7 +652,7 @@ pub fn generate(
                                         CType::TString(s) => s.clone(),
                                         _ => CType::fail("Dependency versions must be strings"),
                                     };
-                                    deps.insert(name, version);
+                                    add_dep(&mut deps, name, version)?;
                                 }
                                 _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                             }
@@ -571,7 +668,7 @@ pub fn generate(
                                     CType::TString(s) => s.clone(),
                                     _ => CType::fail("Dependency versions must be strings"),
                                 };
-                                deps.insert(name, version);
+                                add_dep(&mut deps, name, version)?;
                             }
                             _ => CType::fail("Rust dependencies must be declared with the dependency syntax"),
                         }
@@ -0,0 +1,145 @@
+// Shaders are usually embedded in Alan source as string literals passed to `GPGPU`, so they can
+// be checked while compiling instead of failing when the program first runs them.
+use crate::program::{CType, Microstatement, Program};
+
+/// Turns the Rust string literal representation of an Alan string back into the string itself
+fn unescape(representation: &str) -> String {
+    let inner = representation
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(representation);
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('0') => out.push('\0'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Parses and validates the shader, returning the line within the shader of the problem, if
+/// known, and the error message
+fn check_shader(shader: &str) -> Result<(), (Option<u32>, String)> {
+    let module = naga::front::wgsl::parse_str(shader).map_err(|e| {
+        (
+            e.location(shader).map(|l| l.line_number),
+            e.message().to_string(),
+        )
+    })?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| {
+        (
+            e.location(shader).map(|l| l.line_number),
+            e.as_inner().to_string(),
+        )
+    })?;
+    Ok(())
+}
+
+fn check_microstatement(
+    microstatement: &Microstatement,
+    path: &str,
+    src: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match microstatement {
+        Microstatement::FnCall { function, args } => {
+            if function.name == "GPGPU" {
+                if let Some(Microstatement::Value {
+                    typen,
+                    representation,
+                }) = args.first()
+                {
+                    if let CType::Type(n, _) = &**typen {
+                        if n == "string" {
+                            let shader = unescape(representation);
+                            if let Err((shader_line, message)) = check_shader(&shader) {
+                                // Single-quoted strings are re-quoted in their representation, so
+                                // fall back to finding the shader text itself
+                                let start = src
+                                    .find(representation.as_str())
+                                    .or_else(|| src.find(shader.as_str()));
+                                return Err(match start {
+                                    Some(i) => format!(
+                                        "Invalid WGSL shader at {}:{}: {}",
+                                        path,
+                                        src[..i].matches('\n').count()
+                                            + shader_line.unwrap_or(1) as usize,
+                                        message
+                                    ),
+                                    None => format!("Invalid WGSL shader in {}: {}", path, message),
+                                }
+                                .into());
+                            }
+                        }
+                    }
+                }
+            }
+            for arg in args {
+                check_microstatement(arg, path, src)?;
+            }
+            Ok(())
+        }
+        Microstatement::Assignment { value, .. } => check_microstatement(value, path, src),
+        Microstatement::Closure { function } => {
+            for ms in &function.microstatements {
+                check_microstatement(ms, path, src)?;
+            }
+            Ok(())
+        }
+        Microstatement::VarCall { args, .. } => {
+            for arg in args {
+                check_microstatement(arg, path, src)?;
+            }
+            Ok(())
+        }
+        Microstatement::Array { vals, .. } => {
+            for val in vals {
+                check_microstatement(val, path, src)?;
+            }
+            Ok(())
+        }
+        Microstatement::Return { value: Some(value) } => check_microstatement(value, path, src),
+        _ => Ok(()),
+    }
+}
+
+/// Validates every statically-known shader in the program's own source files, failing with the
+/// Alan source location of the first invalid one. It can be skipped, eg for shaders using features
+/// naga doesn't understand yet, by setting the `ALAN_VALIDATE_SHADERS` environment variable to
+/// `false` or `0`
+pub fn validate(program: &Program) -> Result<(), Box<dyn std::error::Error>> {
+    if program
+        .env
+        .get("ALAN_VALIDATE_SHADERS")
+        .is_some_and(|v| v == "false" || v == "0")
+    {
+        return Ok(());
+    }
+    for (path, (src, _, scope)) in program.scopes_by_file.iter() {
+        if path.starts_with('@') {
+            continue;
+        }
+        for functions in scope.functions.values() {
+            for function in functions {
+                for microstatement in &function.microstatements {
+                    check_microstatement(microstatement, path, src)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
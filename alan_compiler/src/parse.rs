@@ -607,6 +607,14 @@ test!(strn =>
     pass "'str\\'3'";
     pass "\"str\\\"4\"";
 );
+// A format string interpolates the variables named in curly braces, like `f'x is {x}'`
+build!(fmtstrn, and!(token!("f"), strn));
+test!(fmtstrn =>
+    fail "'str'";
+    fail "f 'str'";
+    pass "f'x is {x}'";
+    pass "f\"x is {x}\"";
+);
 named_and!(fulltypename: FullTypename =>
     typename: String as variable,
     opttypegenerics: Option<GnCall> as opt(gncall)
@@ -800,6 +808,7 @@ named_or!(baseassignable: BaseAssignable =>
     FnCall: FnCall as fncall,
     GnCall: GnCall as gncall,
     Array: ArrayBase as arraybase,
+    FmtStrn: String as fmtstrn,
     Variable: String as variable,
     MethodSep: String as and!(optwhitespace, dot, optwhitespace),
     Constants: Constants as constants,
@@ -841,6 +850,7 @@ impl BaseAssignable {
                     .collect::<Vec<String>>()
                     .join("")
             ),
+            BaseAssignable::FmtStrn(s) => s.clone(),
             BaseAssignable::Variable(v) => v.clone(),
             BaseAssignable::MethodSep(_) => ".".to_string(),
             BaseAssignable::Constants(c) => c.to_string(),
@@ -849,6 +859,8 @@ impl BaseAssignable {
 }
 test!(baseassignable =>
     pass "Foo" => "", super::BaseAssignable::Variable("Foo".to_string());
+    pass "f'{x}'" => "", super::BaseAssignable::FmtStrn("f'{x}'".to_string());
+    pass "foo" => "", super::BaseAssignable::Variable("foo".to_string());
 );
 list!(baseassignablelist: BaseAssignable => baseassignable);
 test!(baseassignablelist =>
@@ -973,7 +985,13 @@ named_or!(fullfunctionbody: FullFunctionBody =>
     AssignFunction: AssignFunction as assignfunction,
     DecOnly: String as semicolon,
 );
+/// Consumes nothing and returns the length of the remaining input, so a node can record where in
+/// the source it started (the distance from the end of the source, since that's all nom knows)
+pub fn position(input: &str) -> IResult<&str, usize> {
+    Ok((input, input.len()))
+}
 named_and!(functions: Functions =>
+    pos: usize as position,
     fnn: String as fnn,
     a: String as optwhitespace,
     opttypegenerics: Option<GnCall> as opt(gncall),
@@ -2438,6 +2438,7 @@ impl CType {
                             _ => unreachable!(),
                         }),
                         origin_scope_path: scope.path.clone(),
+                        line: None,
                     }));
                 } else {
                     let mut microstatements = Vec::new();
@@ -2805,6 +2806,7 @@ impl CType {
                         microstatements,
                         kind,
                         origin_scope_path: scope.path.clone(),
+                        line: None,
                     }));
                 }
             }
@@ -2819,6 +2821,7 @@ impl CType {
                     microstatements: Vec::new(),
                     kind: FnKind::Derived,
                     origin_scope_path: scope.path.clone(),
+                    line: None,
                 }));
             }
             CType::Tuple(ts) => {
@@ -2856,6 +2859,7 @@ impl CType {
                                         }],
                                         kind: FnKind::Static,
                                         origin_scope_path: scope.path.clone(),
+                                        line: None,
                                     }));
                                 }
                                 CType::Int(i) => {
@@ -2872,6 +2876,7 @@ impl CType {
                                         }],
                                         kind: FnKind::Static,
                                         origin_scope_path: scope.path.clone(),
+                                        line: None,
                                     }));
                                 }
                                 CType::Float(f) => {
@@ -2891,6 +2896,7 @@ impl CType {
                                         }],
                                         kind: FnKind::Static,
                                         origin_scope_path: scope.path.clone(),
+                                        line: None,
                                     }));
                                 }
                                 CType::Bool(b) => {
@@ -2910,6 +2916,7 @@ impl CType {
                                         }],
                                         kind: FnKind::Static,
                                         origin_scope_path: scope.path.clone(),
+                                        line: None,
                                     }));
                                 }
                                 _ => { /* Do nothing */ }
@@ -2942,6 +2949,7 @@ impl CType {
                                 microstatements: Vec::new(),
                                 kind: FnKind::Derived,
                                 origin_scope_path: scope.path.clone(),
+                                line: None,
                             }));
                         }
                         _otherwise => {
@@ -2952,6 +2960,7 @@ impl CType {
                                 microstatements: Vec::new(),
                                 kind: FnKind::Derived,
                                 origin_scope_path: scope.path.clone(),
+                                line: None,
                             }));
                         }
                     }
@@ -2966,6 +2975,7 @@ impl CType {
                     microstatements: Vec::new(),
                     kind: FnKind::Derived,
                     origin_scope_path: scope.path.clone(),
+                    line: None,
                 }));
             }
             CType::Field(n, f) => {
@@ -2986,6 +2996,7 @@ impl CType {
                             }],
                             kind: FnKind::Static,
                             origin_scope_path: scope.path.clone(),
+                            line: None,
                         }));
                     }
                     CType::Int(i) => {
@@ -3002,6 +3013,7 @@ impl CType {
                             }],
                             kind: FnKind::Static,
                             origin_scope_path: scope.path.clone(),
+                            line: None,
                         }));
                     }
                     CType::Float(f) => {
@@ -3018,6 +3030,7 @@ impl CType {
                             }],
                             kind: FnKind::Static,
                             origin_scope_path: scope.path.clone(),
+                            line: None,
                         }));
                     }
                     CType::Bool(b) => {
@@ -3037,6 +3050,7 @@ impl CType {
                             }],
                             kind: FnKind::Static,
                             origin_scope_path: scope.path.clone(),
+                            line: None,
                         }));
                     }
                     _ => {
@@ -3046,6 +3060,7 @@ impl CType {
                             microstatements: Vec::new(),
                             kind: FnKind::Derived,
                             origin_scope_path: scope.path.clone(),
+                            line: None,
                         }));
                     }
                 }
@@ -3056,6 +3071,7 @@ impl CType {
                     microstatements: Vec::new(),
                     kind: FnKind::Derived,
                     origin_scope_path: scope.path.clone(),
+                    line: None,
                 }));
             }
             CType::Either(ts) => {
@@ -3069,6 +3085,7 @@ impl CType {
                         microstatements: Vec::new(),
                         kind: FnKind::Derived,
                         origin_scope_path: scope.path.clone(),
+                        line: None,
                     }));
                     // Create a store fn to re-assign-and-auto-wrap a value
                     fs.push(Arc::new(Function {
@@ -3080,6 +3097,7 @@ impl CType {
                         microstatements: Vec::new(),
                         kind: FnKind::Derived,
                         origin_scope_path: scope.path.clone(),
+                        line: None,
                     }));
                     if let CType::Void = &**e {
                         // Have a zero-arg constructor function produce the void type, if possible.
@@ -3089,6 +3107,7 @@ impl CType {
                             microstatements: Vec::new(),
                             kind: FnKind::Derived,
                             origin_scope_path: scope.path.clone(),
+                            line: None,
                         }));
                     }
                     // Create the accessor function, the name of the function will
@@ -3103,6 +3122,7 @@ impl CType {
                             microstatements: Vec::new(),
                             kind: FnKind::Derived,
                             origin_scope_path: scope.path.clone(),
+                            line: None,
                         })),
                         CType::Type(n, _) => fs.push(Arc::new(Function {
                             name: n.clone(),
@@ -3113,6 +3133,7 @@ impl CType {
                             microstatements: Vec::new(),
                             kind: FnKind::Derived,
                             origin_scope_path: scope.path.clone(),
+                            line: None,
                         })),
                         _ => {} // We can't make names for other types
                     }
@@ -3130,6 +3151,7 @@ impl CType {
                     microstatements: Vec::new(),
                     kind: FnKind::Derived,
                     origin_scope_path: scope.path.clone(),
+                    line: None,
                 }));
                 let size = match **s {
                     CType::Int(s) => s as usize,
@@ -3151,6 +3173,7 @@ impl CType {
                         microstatements: Vec::new(),
                         kind: FnKind::Derived,
                         origin_scope_path: scope.path.clone(),
+                        line: None,
                     }));
                 }
                 // Also include accessor functions for each
@@ -3161,6 +3184,7 @@ impl CType {
                         microstatements: Vec::new(),
                         kind: FnKind::Derived,
                         origin_scope_path: scope.path.clone(),
+                        line: None,
                     }))
                 }
             }
@@ -3178,6 +3202,7 @@ impl CType {
                     microstatements: Vec::new(),
                     kind: FnKind::DerivedVariadic,
                     origin_scope_path: scope.path.clone(),
+                    line: None,
                 }));
             }
             CType::Int(i) => {
@@ -3194,6 +3219,7 @@ impl CType {
                     }],
                     kind: FnKind::Normal,
                     origin_scope_path: scope.path.clone(),
+                    line: None,
                 }));
             }
             CType::Float(f) => {
@@ -3210,6 +3236,7 @@ impl CType {
                     }],
                     kind: FnKind::Normal,
                     origin_scope_path: scope.path.clone(),
+                    line: None,
                 }));
             }
             CType::Bool(b) => {
@@ -3233,6 +3260,7 @@ impl CType {
                             }],
                             kind: FnKind::Normal,
                             origin_scope_path: scope.path.clone(),
+                            line: None,
                         }));
                     }
                     None => {}
@@ -3251,6 +3279,7 @@ impl CType {
                     }],
                     kind: FnKind::Normal,
                     origin_scope_path: scope.path.clone(),
+                    line: None,
                 }));
             }
             _ => {} // Don't do anything for other types
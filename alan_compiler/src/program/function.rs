@@ -16,6 +16,9 @@ pub struct Function {
     pub microstatements: Vec<Microstatement>,
     pub kind: FnKind,
     pub origin_scope_path: String,
+    /// The (1-indexed) line of the origin scope's source where the function is defined, if it was
+    /// defined directly in Alan source code
+    pub line: Option<usize>,
 }
 
 pub fn type_to_args(t: Arc<CType>) -> Vec<(String, ArgKind, Arc<CType>)> {
@@ -134,6 +137,7 @@ impl Function {
         scope: Scope<'a>,
         function_ast: &parse::Functions,
         is_export: bool,
+        line: Option<usize>,
     ) -> Result<Scope<'a>, Box<dyn std::error::Error>> {
         // In the top-level of a file, all functions *must* be named
         let name = match &function_ast.optname {
@@ -142,7 +146,7 @@ impl Function {
                 return Err("Top-level function without a name!".into());
             }
         };
-        Function::from_ast_with_name(scope, function_ast, is_export, name)
+        Function::from_ast_with_name(scope, function_ast, is_export, name, line)
     }
 
     pub fn from_ast_with_name<'a>(
@@ -150,6 +154,7 @@ impl Function {
         function_ast: &parse::Functions,
         is_export: bool,
         name: String,
+        line: Option<usize>,
     ) -> Result<Scope<'a>, Box<dyn std::error::Error>> {
         if let Some(generics) = &function_ast.opttypegenerics {
             // We are going to conditionally compile this type declaration. If the we get true, we
@@ -310,6 +315,7 @@ impl Function {
                         microstatements: Vec::new(),
                         kind,
                         origin_scope_path: scope.path.clone(),
+                        line,
                     });
                     if is_export {
                         scope
@@ -570,6 +576,7 @@ impl Function {
             microstatements,
             kind,
             origin_scope_path: scope.path.clone(),
+            line,
         });
         if is_export {
             scope
@@ -695,6 +702,7 @@ impl Function {
                     microstatements,
                     kind,
                     origin_scope_path: scope.path.clone(),
+                    line: generic_function.line,
                 });
                 if scope.functions.contains_key(&f.name) {
                     let func_vec = scope.functions.get_mut(&f.name).unwrap();
@@ -803,6 +811,7 @@ impl Function {
                     microstatements,
                     kind,
                     origin_scope_path: scope.path.clone(),
+                    line: generic_function.line,
                 });
                 if scope.functions.contains_key(&f.name) {
                     let func_vec = scope.functions.get_mut(&f.name).unwrap();
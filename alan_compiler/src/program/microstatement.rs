@@ -47,6 +47,13 @@ pub enum Microstatement {
         typen: Arc<CType>,
         vals: Vec<Microstatement>,
     },
+    /// A format string, with the literal text around each of the (already `string`) values, so
+    /// there is always one more literal than there are values
+    Format {
+        typen: Arc<CType>,
+        literals: Vec<String>,
+        values: Vec<Microstatement>,
+    },
     Return {
         value: Option<Box<Microstatement>>,
     }, // TODO: Conditionals
@@ -57,6 +64,7 @@ impl Microstatement {
         match self {
             Self::Value { typen, .. } => typen.clone(),
             Self::Array { typen, .. } => typen.clone(),
+            Self::Format { typen, .. } => typen.clone(),
             Self::Arg { typen, .. } => typen.clone(),
             Self::Assignment { value, .. } => value.get_type(),
             Self::Return { value } => match value {
@@ -108,6 +116,173 @@ enum BaseChunk<'a> {
     Array(&'a parse::ArrayBase),
     Variable(&'a String),
     Constant(&'a parse::Constants),
+    Format(&'a String),
+}
+
+/// Resolves the named function for the given arguments and returns the call to it
+fn call_by_name<'a>(
+    mut scope: Scope<'a>,
+    name: &str,
+    args: Vec<Microstatement>,
+) -> Result<(Scope<'a>, Microstatement), Box<dyn std::error::Error>> {
+    let mut temp_scope = scope.child();
+    let mut arg_types = Vec::new();
+    for m in &args {
+        arg_types.push(m.get_type());
+        // In case the type constructor has not already been created
+        let t = m.get_type();
+        temp_scope = CType::from_ctype(temp_scope, t.clone().to_callable_string(), t);
+    }
+    match temp_scope.resolve_function(&name.to_string(), &arg_types) {
+        Some((mut temp_scope, f)) => {
+            temp_scope
+                .functions
+                .insert(name.to_string(), vec![f.clone()]);
+            merge!(scope, temp_scope);
+            Ok((scope, Microstatement::FnCall { function: f, args }))
+        }
+        None => Err(format!(
+            "A function with the signature {}({}) does not exist",
+            name,
+            arg_types
+                .iter()
+                .map(|a| a.clone().to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+        .into()),
+    }
+}
+
+/// Converts a single-quoted string literal into the double-quoted representation the backends use
+fn double_quoted(s: &str) -> String {
+    if s.starts_with('"') {
+        s.to_string()
+    } else {
+        // TODO: Is there a cheaper way to do this conversion?
+        s.replace('\"', "\\\"")
+            .replace("\\'", "\\\\\"")
+            .replace('\'', "\"")
+            .replace("\\\\\"", "'")
+    }
+}
+
+/// Splits a format string like `f'x is {x}'` into the literal text and the variables named in
+/// curly braces, each converted to a `string`. The variables are resolved like any other variable
+/// reference, so the nearest binding of the name is used. A doubled `{{` or `}}` is a literal brace.
+fn format_string<'a>(
+    representation: String,
+    parent_fn: Option<&Function>,
+    mut scope: Scope<'a>,
+    mut microstatements: Vec<Microstatement>,
+) -> Result<(Scope<'a>, Vec<Microstatement>, Microstatement), Box<dyn std::error::Error>> {
+    let string = scope.resolve_type("string").unwrap().clone();
+    let mut literals = Vec::new();
+    let mut values = Vec::new();
+    let mut literal = String::new();
+    // The representation is always double-quoted at this point
+    let mut rest = &representation[1..representation.len() - 1];
+    while let Some(i) = rest.find(['{', '}', '\\']) {
+        literal.push_str(&rest[..i]);
+        let (c, after) = rest[i..].split_at(1);
+        rest = after;
+        match c {
+            // Escape sequences are passed through as-is, so an escaped character is never a brace
+            "\\" => {
+                literal.push('\\');
+                if let Some(e) = rest.chars().next() {
+                    literal.push(e);
+                    rest = &rest[e.len_utf8()..];
+                }
+            }
+            "}" => match rest.strip_prefix('}') {
+                Some(after) => {
+                    literal.push('}');
+                    rest = after;
+                }
+                None => {
+                    return Err(format!(
+                        "Unmatched `}}` in format string {}. Use `}}}}` for a literal `}}`",
+                        representation
+                    )
+                    .into());
+                }
+            },
+            _ => {
+                if let Some(after) = rest.strip_prefix('{') {
+                    literal.push('{');
+                    rest = after;
+                    continue;
+                }
+                let name = match rest.find('}') {
+                    Some(end) => &rest[..end],
+                    None => {
+                        return Err(format!(
+                            "Unmatched `{{` in format string {}. Use `{{{{` for a literal `{{`",
+                            representation
+                        )
+                        .into());
+                    }
+                };
+                if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                {
+                    return Err(format!(
+                        "Only variables can be interpolated in format strings, but found `{{{}}}` in {}. Use `{{{{` and `}}}}` for literal braces",
+                        name, representation
+                    )
+                    .into());
+                }
+                rest = &rest[name.len() + 1..];
+                let res = baseassignablelist_to_microstatements(
+                    &[parse::BaseAssignable::Variable(name.to_string())],
+                    parent_fn,
+                    scope,
+                    microstatements,
+                )?;
+                scope = res.0;
+                microstatements = res.1;
+                let value = microstatements.pop().unwrap();
+                if let CType::Function(..) | CType::AnyOf(..) = &*value.get_type() {
+                    return Err(format!(
+                        "Couldn't find variable {} to interpolate in format string {}",
+                        name, representation
+                    )
+                    .into());
+                }
+                let value = if value.get_type() == string {
+                    value
+                } else {
+                    let res = call_by_name(scope, "string", vec![value])?;
+                    scope = res.0;
+                    res.1
+                };
+                literals.push(std::mem::take(&mut literal));
+                values.push(value);
+            }
+        }
+    }
+    literal.push_str(rest);
+    if values.is_empty() {
+        return Ok((
+            scope,
+            microstatements,
+            Microstatement::Value {
+                typen: string,
+                representation: format!("\"{}\"", literal),
+            },
+        ));
+    }
+    literals.push(literal);
+    Ok((
+        scope,
+        microstatements,
+        Microstatement::Format {
+            typen: string,
+            literals,
+            values,
+        },
+    ))
 }
 
 pub fn baseassignablelist_to_microstatements<'a>(
@@ -255,6 +430,7 @@ pub fn baseassignablelist_to_microstatements<'a>(
             (None, Some(parse::BaseAssignable::Constants(c)), _, _, _) => {
                 (BaseChunk::Constant(c), 1)
             }
+            (None, Some(parse::BaseAssignable::FmtStrn(s)), _, _, _) => (BaseChunk::Format(s), 1),
             (Some(_), Some(parse::BaseAssignable::Array(a)), _, _, _) => {
                 (BaseChunk::ArrayAccessor(a), 1)
             }
@@ -290,15 +466,7 @@ pub fn baseassignablelist_to_microstatements<'a>(
                         let string = scope.resolve_type("string").unwrap().clone();
                         prior_value = Some(Microstatement::Value {
                             typen: string,
-                            representation: if s.starts_with('"') {
-                                s.clone()
-                            } else {
-                                // TODO: Is there a cheaper way to do this conversion?
-                                s.replace('\"', "\\\"")
-                                    .replace("\\'", "\\\\\"")
-                                    .replace('\'', "\"")
-                                    .replace("\\\\\"", "'")
-                            },
+                            representation: double_quoted(s),
                         });
                     }
                     parse::Constants::Num(n) => match n {
@@ -323,8 +491,17 @@ pub fn baseassignablelist_to_microstatements<'a>(
                     },
                 }
             }
+            BaseChunk::Format(s) => {
+                // Drop the `f` prefix
+                let res = format_string(double_quoted(&s[1..]), parent_fn, scope, microstatements)?;
+                scope = res.0;
+                microstatements = res.1;
+                prior_value = Some(res.2);
+            }
             BaseChunk::Variable(v) => {
-                let typen = match microstatements.iter().find(|m| match m {
+                // The most recent binding of the name is used, so a redeclared or shadowed variable
+                // refers to the nearest one
+                let typen = match microstatements.iter().rev().find(|m| match m {
                     Microstatement::Assignment { name, .. } => v == name,
                     Microstatement::Arg { name, .. } => v == name,
                     _ => false,
@@ -655,6 +832,7 @@ pub fn baseassignablelist_to_microstatements<'a>(
                     microstatements: ms,
                     kind,
                     origin_scope_path: scope.path.clone(),
+                    line: None,
                 });
                 prior_value = Some(Microstatement::Closure { function });
             }
@@ -20,5 +20,5 @@ pub use function::Function;
 pub use microstatement::Microstatement;
 pub use operatormapping::OperatorMapping;
 pub use program::Program;
-pub use scope::Scope;
+pub use scope::{FnGenerator, Generated, Scope};
 pub use typeoperatormapping::TypeOperatorMapping;
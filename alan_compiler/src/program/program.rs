@@ -1,7 +1,11 @@
 use std::cell::Cell;
+use std::collections::HashSet;
 use std::fs::read_to_string;
 use std::pin::Pin;
 
+use super::FnKind;
+use super::Microstatement;
+use super::OperatorMapping;
 use super::Scope;
 use crate::parse;
 
@@ -58,6 +62,8 @@ Program {
 
 thread_local!(static TARGET_LANG_RS: Cell<bool> = const { Cell::new(true) });
 
+thread_local!(static PARSE_COUNT: Cell<usize> = const { Cell::new(0) });
+
 impl<'a> Program<'a> {
     pub fn load(path: String) -> Result<(), Box<dyn std::error::Error>> {
         let program = Program::get_program();
@@ -88,6 +94,103 @@ impl<'a> Program<'a> {
         }
     }
 
+    /// Returns a warning for every name imported into the specified file that is never referenced
+    /// by the rest of that file. Functions are checked against the resolved microstatements of the
+    /// functions defined in the file, while types (which are erased during resolution) and
+    /// functions used only within not-yet-realized generic functions fall back to an identifier
+    /// search of the source text outside of the import statements themselves.
+    pub fn unused_imports(&self, path: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let (src, ast, scope) = match self.scopes_by_file.get(path) {
+            Some(s) => s,
+            None => return Err(format!("Could not find a scope for file {}", path).into()),
+        };
+        let mut imported_types = Vec::new();
+        let mut imported_fns = Vec::new();
+        for import in import_statements(ast) {
+            // Re-exported imports are part of this file's interface, so they are never unused
+            if import.is_export {
+                continue;
+            }
+            if import.is_type {
+                imported_types.push(import.name);
+            } else {
+                imported_fns.push(import.name);
+            }
+        }
+        let mut used = HashSet::new();
+        let mut has_generics = false;
+        for funcs in scope.functions.values() {
+            for f in funcs.iter().filter(|f| f.origin_scope_path == path) {
+                if let FnKind::Generic(..) = &f.kind {
+                    has_generics = true;
+                }
+                for ms in &f.microstatements {
+                    used_names(ms, &mut used);
+                }
+            }
+        }
+        for opmap in scope.operatormappings.values() {
+            match opmap {
+                OperatorMapping::Prefix { functionname, .. }
+                | OperatorMapping::Infix { functionname, .. }
+                | OperatorMapping::Postfix { functionname, .. } => {
+                    used.insert(functionname.clone());
+                }
+            }
+        }
+        // The textual fallback ignores the import lines themselves
+        let body_src = src
+            .lines()
+            .filter(|l| !l.contains("<--"))
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let mentions = |text: &str, name: &str| {
+            text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .any(|ident| ident == name)
+        };
+        let mentioned = |name: &str| mentions(&body_src, name);
+        let mut warnings = Vec::new();
+        let mut warn = |name: &str| {
+            let line = src
+                .lines()
+                .position(|l| l.contains("<--") && mentions(l, name))
+                .map(|i| i + 1)
+                .unwrap_or(0);
+            warnings.push(format!("{}:{}: unused import `{}`", path, line, name));
+        };
+        for name in &imported_fns {
+            if !used.contains(name) && (!has_generics || !mentioned(name)) {
+                warn(name);
+            }
+        }
+        for name in &imported_types {
+            if !mentioned(name) {
+                warn(name);
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Returns the entry file and every `.ln` file it transitively imports, in breadth-first order
+    /// of first import. The standard library is not included. The files must already be loaded.
+    pub fn imported_files(&self, entry: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut files = vec![entry.to_string()];
+        let mut i = 0;
+        while i < files.len() {
+            let ast = match self.scopes_by_file.get(&files[i]) {
+                Some((_, ast, _)) => ast,
+                None => return Err(format!("Could not find a scope for file {}", files[i]).into()),
+            };
+            for import in import_statements(ast) {
+                if !import.dep.starts_with('@') && !files.contains(&import.dep) {
+                    files.push(import.dep);
+                }
+            }
+            i += 1;
+        }
+        Ok(files)
+    }
+
     pub fn get_program() -> Program<'static> {
         if TARGET_LANG_RS.get() {
             PROGRAM_RS.take()
@@ -104,6 +207,37 @@ impl<'a> Program<'a> {
         }
     }
 
+    /// The Rust and Javascript programs resolve the source files differently, but the parsed AST
+    /// of a file is the same for both, so when generating multiple targets in one invocation each
+    /// file only needs to be parsed once. This finds an existing parse of the exact same source.
+    pub fn cached_ast(path: &str, src: &str) -> Option<parse::Ln> {
+        let find = |p: &Program| {
+            p.scopes_by_file
+                .get(path)
+                .filter(|(txt, _, _)| txt.as_str() == src)
+                .map(|(_, ast, _)| ast.clone())
+        };
+        let program = PROGRAM_RS.take();
+        let ast = find(&program);
+        PROGRAM_RS.set(program);
+        if ast.is_some() {
+            return ast;
+        }
+        let program = PROGRAM_JS.take();
+        let ast = find(&program);
+        PROGRAM_JS.set(program);
+        ast
+    }
+
+    /// The number of source files parsed by the current thread so far
+    pub fn parse_count() -> usize {
+        PARSE_COUNT.get()
+    }
+
+    pub fn record_parse() {
+        PARSE_COUNT.set(PARSE_COUNT.get() + 1);
+    }
+
     pub fn set_target_lang_js() {
         TARGET_LANG_RS.set(false);
     }
@@ -116,3 +250,106 @@ impl<'a> Program<'a> {
         TARGET_LANG_RS.get()
     }
 }
+
+fn used_names(ms: &Microstatement, used: &mut HashSet<String>) {
+    match ms {
+        Microstatement::Assignment { value, .. } => used_names(value, used),
+        Microstatement::Arg { .. } => {}
+        Microstatement::FnCall { function, args } => {
+            used.insert(function.name.clone());
+            for arg in args {
+                used_names(arg, used);
+            }
+        }
+        Microstatement::Closure { function } => {
+            for ms in &function.microstatements {
+                used_names(ms, used);
+            }
+        }
+        Microstatement::VarCall { name, args, .. } => {
+            used.insert(name.clone());
+            for arg in args {
+                used_names(arg, used);
+            }
+        }
+        Microstatement::Value { representation, .. } => {
+            // Function references passed as values are represented by their name
+            used.insert(representation.clone());
+        }
+        Microstatement::Array { vals, .. } => {
+            for val in vals {
+                used_names(val, used);
+            }
+        }
+        Microstatement::Format { values, .. } => {
+            for value in values {
+                used_names(value, used);
+            }
+        }
+        Microstatement::Return { value } => {
+            if let Some(v) = value {
+                used_names(v, used);
+            }
+        }
+    }
+}
+
+struct Import {
+    name: String,
+    dep: String,
+    is_type: bool,
+    is_export: bool,
+}
+
+/// Finds the `name <-- 'dependency'` import statements at the top level of a file
+fn import_statements(ast: &parse::Ln) -> Vec<Import> {
+    let mut imports = Vec::new();
+    for element in &ast.body {
+        let import = match element {
+            parse::RootElements::Types(t) => type_import(t, false),
+            parse::RootElements::Functions(f) => fn_import(f, false),
+            parse::RootElements::Exports(e) => match &e.exportable {
+                parse::Exportable::Types(t) => type_import(t, true),
+                parse::Exportable::Functions(f) => fn_import(f, true),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(import) = import {
+            imports.push(import);
+        }
+    }
+    imports
+}
+
+fn type_import(t: &parse::Types, is_export: bool) -> Option<Import> {
+    Some(Import {
+        name: t.fulltypename.to_string(),
+        dep: import_dep(&t.typedef.typeassignables)?,
+        is_type: true,
+        is_export,
+    })
+}
+
+fn fn_import(f: &parse::Functions, is_export: bool) -> Option<Import> {
+    match (&f.optname, &f.opttype, &f.fullfunctionbody) {
+        (Some(name), Some(opttype), parse::FullFunctionBody::DecOnly(_)) => Some(Import {
+            name: name.clone(),
+            dep: import_dep(opttype)?,
+            is_type: false,
+            is_export,
+        }),
+        _ => None,
+    }
+}
+
+fn import_dep(typeassignables: &[parse::WithTypeOperators]) -> Option<String> {
+    typeassignables
+        .iter()
+        .map(|ta| ta.to_string())
+        .collect::<Vec<String>>()
+        .join("")
+        .trim()
+        .strip_prefix("<--")
+        .map(|d| d.trim().trim_matches(|c| c == '\'' || c == '"').to_string())
+}
@@ -13,6 +13,22 @@ use super::Program;
 use super::TypeOperatorMapping;
 use crate::parse;
 
+/// Generated code keyed by name, along with the dependencies it needs
+pub type Generated = (
+    OrderedHashMap<String, String>,
+    OrderedHashMap<String, String>,
+);
+
+/// A backend's function generator, which adds the named function (and anything it needs) to the
+/// generated code
+pub type FnGenerator = fn(
+    String,
+    &Function,
+    &Scope,
+    OrderedHashMap<String, String>,
+    OrderedHashMap<String, String>,
+) -> Result<Generated, Box<dyn std::error::Error>>;
+
 #[derive(Clone, Debug)]
 pub struct Scope<'a> {
     pub path: String,
@@ -32,8 +48,11 @@ impl<'a> Scope<'a> {
     pub fn load_scope(
         mut s: Scope<'a>,
         ast: &parse::Ln,
+        src: &str,
         is_root: bool,
     ) -> Result<Scope<'a>, Box<dyn std::error::Error>> {
+        // The parser records positions as the length of the source remaining after them
+        let line = |pos: usize| Some(src[..src.len() - pos].matches('\n').count() + 1);
         for (i, element) in ast.body.iter().enumerate() {
             match element {
                 parse::RootElements::Types(t) => {
@@ -41,7 +60,9 @@ impl<'a> Scope<'a> {
                     s = res.0;
                 }
 
-                parse::RootElements::Functions(f) => s = Function::from_ast(s, f, false)?,
+                parse::RootElements::Functions(f) => {
+                    s = Function::from_ast(s, f, false, line(f.pos))?
+                }
                 parse::RootElements::ConstDeclaration(c) => s = Const::from_ast(s, c, false)?,
                 parse::RootElements::OperatorMapping(o) => {
                     s = OperatorMapping::from_ast(s, o, false)?
@@ -50,7 +71,9 @@ impl<'a> Scope<'a> {
                     s = TypeOperatorMapping::from_ast(s, o, false)?
                 }
                 parse::RootElements::Exports(e) => match &e.exportable {
-                    parse::Exportable::Functions(f) => s = Function::from_ast(s, f, true)?,
+                    parse::Exportable::Functions(f) => {
+                        s = Function::from_ast(s, f, true, line(f.pos))?
+                    }
                     parse::Exportable::ConstDeclaration(c) => s = Const::from_ast(s, c, true)?,
                     parse::Exportable::OperatorMapping(o) => {
                         s = OperatorMapping::from_ast(s, o, true)?
@@ -127,7 +150,7 @@ impl<'a> Scope<'a> {
                 typeoperatormappings: OrderedHashMap::new(),
                 exports: OrderedHashMap::new(),
             };
-            Scope::load_scope(s, ast, true).expect("Invalid root scope definition")
+            Scope::load_scope(s, ast, ROOT_SRC, true).expect("Invalid root scope definition")
         };
         if Program::is_target_lang_rs() {
             ROOT_SCOPE_RS.get_or_init(resolver)
@@ -138,8 +161,15 @@ impl<'a> Scope<'a> {
     pub fn from_src(path: &str, src: String) -> Result<(), Box<dyn std::error::Error>> {
         let txt = Box::pin(src);
         let txt_ptr: *const str = &**txt;
-        // *How* would this move, anyways? But TODO: See if there's a way to handle this safely
-        let ast = unsafe { parse::get_ast(&*txt_ptr)? };
+        let ast = match Program::cached_ast(path, &txt) {
+            Some(ast) => ast,
+            None => {
+                Program::record_parse();
+                // *How* would this move, anyways? But TODO: See if there's a way to handle this
+                // safely
+                unsafe { parse::get_ast(&*txt_ptr)? }
+            }
+        };
         let mut s = Scope {
             path: path.to_string(),
             parent: Some(Scope::root()),
@@ -150,7 +180,8 @@ impl<'a> Scope<'a> {
             typeoperatormappings: OrderedHashMap::new(),
             exports: OrderedHashMap::new(),
         };
-        s = Scope::load_scope(s, &ast, false)?;
+        s = Scope::load_scope(s, &ast, &txt, false)?;
+        s.check_duplicate_functions()?;
         let mut program = Program::get_program();
         program
             .scopes_by_file
@@ -159,6 +190,165 @@ impl<'a> Scope<'a> {
         Ok(())
     }
 
+    /// Overloads are allowed as long as their argument types differ. Two functions defined in the
+    /// same file with the same name and identical argument types would be ambiguous, so that is a
+    /// hard error that points at both definitions.
+    fn check_duplicate_functions(&self) -> Result<(), Box<dyn std::error::Error>> {
+        for (name, fns) in self.functions.iter() {
+            // Functions are inserted at the front of the list, so reverse it to get them back into
+            // definition order. Only functions written in this file have a line, which skips
+            // imported, derived, and generated functions.
+            let defined = fns
+                .iter()
+                .rev()
+                .filter(|f| f.origin_scope_path == self.path)
+                .filter_map(|f| f.line.map(|line| (f, line)))
+                .collect::<Vec<(&Arc<Function>, usize)>>();
+            let signatures = defined
+                .iter()
+                .map(|(f, _)| {
+                    f.args()
+                        .into_iter()
+                        .map(|(_, _, t)| t.to_strict_string(true))
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                })
+                .collect::<Vec<String>>();
+            for (i, a) in signatures.iter().enumerate() {
+                for (j, b) in signatures.iter().enumerate().skip(i + 1) {
+                    if a == b {
+                        return Err(format!(
+                            "Duplicate definition of {}({}) at {}:{} and {}:{}",
+                            name, a, self.path, defined[i].1, self.path, defined[j].1,
+                        )
+                        .into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the exported, zero-argument functions whose names start with `test_`, in the order
+    /// they were defined. In test mode these are run by a generated harness if there is no entry
+    /// function.
+    pub fn test_functions(&self) -> Vec<(String, Arc<Function>)> {
+        self.functions
+            .iter()
+            .filter(|(name, fs)| {
+                name.starts_with("test_")
+                    && self.exports.contains_key(*name)
+                    && fs.len() == 1
+                    && fs[0].args().is_empty()
+            })
+            .map(|(name, fs)| (name.clone(), fs[0].clone()))
+            .collect()
+    }
+
+    /// In test mode, an entry file without the entry function can instead export `test_` functions
+    /// that are all run by a generated `main` function. If that is the case, this generates each of
+    /// the test functions with the given backend, which must return a `bool` for whether they
+    /// passed, and the `main` function `main_fn` generates from the test names to run them all.
+    /// Otherwise it returns `None` and the entry function should be generated instead.
+    pub fn test_harness(
+        &self,
+        entry_fn: &str,
+        test_mode: bool,
+        fn_generate: FnGenerator,
+        main_fn: impl Fn(&[&str]) -> String,
+    ) -> Result<Option<Generated>, Box<dyn std::error::Error>> {
+        let tests = self.test_functions();
+        if !test_mode || tests.is_empty() || self.functions.contains_key(entry_fn) {
+            return Ok(None);
+        }
+        let mut fns = OrderedHashMap::new();
+        let mut deps = OrderedHashMap::new();
+        for (name, func) in &tests {
+            match &*func.rettype() {
+                CType::Type(n, _) if n == "bool" => {}
+                _ => return Err(format!("Test function `{}` must return a `bool`", name).into()),
+            }
+            (fns, deps) = fn_generate(name.clone(), func, self, fns, deps)?;
+        }
+        let names = tests
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<&str>>();
+        fns.insert("main".to_string(), main_fn(&names));
+        Ok(Some((fns, deps)))
+    }
+
+    /// Finds the entry function of the entry file, which must be exported, defined only once, and
+    /// take no arguments. It could have a return type, but the backends decide what to do with it.
+    pub fn entry_function(
+        &self,
+        entry_file: &str,
+        entry_fn: &str,
+    ) -> Result<&Arc<Function>, Box<dyn std::error::Error>> {
+        // Without support for building shared libs yet, assume there is an exported entry function
+        // (`main` unless otherwise specified) in the entry file or fail otherwise
+        match self.exports.get(entry_fn) {
+            Some(_) => {}
+            None if self.functions.contains_key(entry_fn) => {
+                return Err(format!(
+                    "Entry file `{0}` has a `{1}` function but it is not exported. Add `export` to it: `export fn {1}`",
+                    entry_file, entry_fn
+                )
+                .into());
+            }
+            None => {
+                // List what the file does export, in case the wrong file or name was used
+                let found = match self.exports.len() {
+                    0 => "It does not export anything.".to_string(),
+                    _ => format!(
+                        "Exported names found: {}",
+                        self.exports
+                            .keys()
+                            .map(|k| format!("`{}`", k))
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    ),
+                };
+                return Err(format!(
+                    "Entry file `{}` has no function named `{}`. It must define and export one. {}",
+                    entry_file, entry_fn, found
+                )
+                .into());
+            }
+        };
+        // Getting here *should* guarantee that the entry function exists, so let's grab it.
+        let func = match self.functions.get(entry_fn) {
+            Some(f) => f,
+            None => {
+                return Err(
+                    "An export has been found without a definition. This should be impossible."
+                        .into(),
+                );
+            }
+        };
+        // Make sure there is only a single entry function, since *usually* you're allowed to have
+        // multiple functions with the same name as long as they have different arguments.
+        if func.len() != 1 {
+            return Err(format!(
+                "Entry function `{}` in `{}` has {} definitions, but the entry function can't be overloaded",
+                entry_fn,
+                entry_file,
+                func.len()
+            )
+            .into());
+        }
+        if !func[0].args().is_empty() {
+            return Err(format!(
+                "Entry function `{}` in `{}` takes {} argument(s), but the entry function must take none",
+                entry_fn,
+                entry_file,
+                func[0].args().len()
+            )
+            .into());
+        }
+        Ok(&func[0])
+    }
+
     pub fn child<'b>(&'a self) -> Scope<'b>
     where
         'a: 'b,
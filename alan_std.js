@@ -686,6 +686,52 @@ export class Str {
   }
 }
 
+export function lines(s) {
+  // Matches Rust's `str::lines`: a trailing line ending doesn't create an empty last line
+  let out = s.val
+    .split("\n")
+    .map((l, i, a) => (i < a.length - 1 && l.endsWith("\r") ? l.slice(0, -1) : l));
+  if (out[out.length - 1] === "") {
+    out.pop();
+  }
+  return out.map((l) => new Str(l));
+}
+
+export function median(a) {
+  if (a.length === 0) {
+    return null;
+  }
+  let sorted = a.map((v) => v.val).sort((x, y) => x - y);
+  let mid = Math.floor(sorted.length / 2);
+  return new F64(sorted.length % 2 === 0 ? (sorted[mid - 1] + sorted[mid]) / 2 : sorted[mid]);
+}
+
+export function mode(a) {
+  let counts = new Map();
+  for (let v of a) {
+    counts.set(v.val, (counts.get(v.val) ?? 0) + 1);
+  }
+  let out = null;
+  let best = 0;
+  for (let [v, c] of counts) {
+    // Ties go to the smallest value
+    if (c > best || (c === best && v < out)) {
+      out = v;
+      best = c;
+    }
+  }
+  return out === null ? null : new I64(out);
+}
+
+export function variance(a, sample) {
+  let n = sample.val ? a.length - 1 : a.length;
+  if (n <= 0) {
+    return null;
+  }
+  let mean = a.reduce((s, v) => s + v.val, 0) / a.length;
+  return new F64(a.reduce((s, v) => s + (v.val - mean) * (v.val - mean), 0) / n);
+}
+
 export function swap(a, i, j) {
   if (i.val < 0 || i.val > a.length) {
     return new AlanError(`Provided index ${i.val} is beyond the bounds of the array`);
@@ -698,6 +744,50 @@ export function swap(a, i, j) {
   a[j.val] = temp;
 }
 
+export function equals(a, b) {
+  if (a instanceof Array) {
+    return new Bool(a.length === b.length && a.every((v, i) => equals(v, b[i]).val));
+  }
+  return new Bool(a.val === b.val);
+}
+
+export function approxEquals(a, b, epsilon) {
+  return new Bool(
+    a.length === b.length && a.every((v, i) => Math.abs(v.val - b[i].val) <= epsilon.val)
+  );
+}
+
+function zipVecF64(a, b, op, f) {
+  if (a.length !== b.length) {
+    return new AlanError(`Elementwise ${op} requires equal-length arrays, got lengths ${a.length} and ${b.length}`);
+  }
+  return a.map((v, i) => new F64(f(v.val, b[i].val)));
+}
+
+export function addVecF64(a, b) {
+  return zipVecF64(a, b, 'addition', (x, y) => x + y);
+}
+
+export function subVecF64(a, b) {
+  return zipVecF64(a, b, 'subtraction', (x, y) => x - y);
+}
+
+export function mulVecF64(a, b) {
+  return zipVecF64(a, b, 'multiplication', (x, y) => x * y);
+}
+
+export function divVecF64(a, b) {
+  let i = b.findIndex((v) => v.val === 0);
+  if (i !== -1) {
+    return new AlanError(`Elementwise division by zero at index ${i}`);
+  }
+  return zipVecF64(a, b, 'division', (x, y) => x / y);
+}
+
+export function scaleVecF64(a, s) {
+  return a.map((v) => new F64(v.val * s.val));
+}
+
 async function merge(left, right, sorter) {
   let arr = [];
   while (left.length && right.length) {
@@ -727,6 +817,23 @@ export async function sort(a, sorter) {
   }
 }
 
+export function mapRange(x, inLo, inHi, outLo, outHi) {
+  if (inLo.val === inHi.val) {
+    return new AlanError("Cannot map from an empty range");
+  }
+  return new F64(outLo.val + (x.val - inLo.val) * (outHi.val - outLo.val) / (inHi.val - inLo.val));
+}
+
+export function mapRangeClamped(x, inLo, inHi, outLo, outHi) {
+  let v = mapRange(x, inLo, inHi, outLo, outHi);
+  if (v instanceof AlanError) {
+    return v;
+  }
+  let lo = Math.min(outLo.val, outHi.val);
+  let hi = Math.max(outLo.val, outHi.val);
+  return new F64(Math.min(Math.max(v.val, lo), hi));
+}
+
 export function cross(a, b) {
   // Assuming they're all the same type
   let type = a[0].constructor;
@@ -737,6 +844,246 @@ export function cross(a, b) {
   ];
 }
 
+export function angleBetween(a, b) {
+  let dot = a[0].val * b[0].val + a[1].val * b[1].val + a[2].val * b[2].val;
+  let magA = Math.sqrt(a[0].val ** 2 + a[1].val ** 2 + a[2].val ** 2);
+  let magB = Math.sqrt(b[0].val ** 2 + b[1].val ** 2 + b[2].val ** 2);
+  if (magA === 0 || magB === 0) {
+    return new AlanError("Cannot compute the angle of a zero-length vector");
+  }
+  return new F32(Math.acos(Math.min(Math.max(dot / (magA * magB), -1), 1)));
+}
+
+export class RngState {
+  // A SplitMix64 generator, matching the Rust implementation output-for-output
+  constructor(seed) {
+    this.state = BigInt.asUintN(64, BigInt(seed));
+  }
+}
+
+export function seedRng(seed) {
+  return new RngState(seed.val);
+}
+
+export function nextI64(rng) {
+  rng.state = BigInt.asUintN(64, rng.state + 0x9e3779b97f4a7c15n);
+  let z = rng.state;
+  z = BigInt.asUintN(64, (z ^ (z >> 30n)) * 0xbf58476d1ce4e5b9n);
+  z = BigInt.asUintN(64, (z ^ (z >> 27n)) * 0x94d049bb133111ebn);
+  return new I64(BigInt.asIntN(64, z ^ (z >> 31n)));
+}
+
+export function nextF64(rng) {
+  let z = BigInt.asUintN(64, nextI64(rng).val);
+  return new F64(Number(z >> 11n) / 2 ** 53);
+}
+
+export class ExecResult {
+  constructor(stdout, stderr, code) {
+    this.stdout = new Str(stdout);
+    this.stderr = new Str(stderr);
+    this.code = new I64(code);
+  }
+}
+
+export async function exec(cmd, args) {
+  let { spawnSync } = await import('node:child_process');
+  let res = spawnSync(cmd.val, args.map((a) => a.val), {
+    encoding: 'utf8',
+    stdio: ['ignore', 'pipe', 'pipe'],
+  });
+  if (res.error) {
+    return new AlanError(`Could not run ${cmd.val}: ${res.error.message}`);
+  }
+  return new ExecResult(res.stdout, res.stderr, res.status ?? -1);
+}
+
+export class LruCache {
+  constructor(capacity) {
+    this.capacity = Math.max(Number(capacity.valueOf()), 0);
+    this.map = new Map();
+  }
+
+  get(k) {
+    let key = k?.val ?? k;
+    if (!this.map.has(key)) {
+      return null;
+    }
+    // Maps iterate in insertion order, so re-inserting marks the key as the most recently used
+    let v = this.map.get(key);
+    this.map.delete(key);
+    this.map.set(key, v);
+    return v;
+  }
+
+  put(k, v) {
+    let key = k?.val ?? k;
+    if (this.capacity === 0) {
+      return v;
+    }
+    if (this.map.has(key)) {
+      this.map.delete(key);
+      this.map.set(key, v);
+      return null;
+    }
+    let evicted = null;
+    if (this.map.size >= this.capacity) {
+      let oldest = this.map.keys().next().value;
+      evicted = this.map.get(oldest);
+      this.map.delete(oldest);
+    }
+    this.map.set(key, v);
+    return evicted;
+  }
+
+  len() {
+    return new I64(this.map.size);
+  }
+}
+
+export class Memoized {
+  constructor(f) {
+    this.f = f;
+    this.cache = new Map();
+    this.invocations = 0;
+  }
+
+  async call(k) {
+    let key = k?.val ?? k;
+    if (this.cache.has(key)) {
+      return this.cache.get(key);
+    }
+    let v = await this.f(k);
+    this.invocations++;
+    this.cache.set(key, v);
+    return v;
+  }
+
+  calls() {
+    return new I64(this.invocations);
+  }
+}
+
+export function toJson(d) {
+  // Built by hand because JS objects reorder integer-like keys, but we want to preserve key order
+  return new Str(`{${[...d].map(([k, v]) => `${JSON.stringify(k)}:${JSON.stringify(v.val)}`).join(',')}}`);
+}
+
+export function parseJson(s) {
+  let obj;
+  try {
+    obj = JSON.parse(s.val);
+  } catch (e) {
+    return new AlanError(`Invalid JSON: ${e.message}`);
+  }
+  if (obj === null || typeof obj !== 'object' || Array.isArray(obj)) {
+    return new AlanError('Expected a JSON object');
+  }
+  let out = new Map();
+  for (let [k, v] of Object.entries(obj)) {
+    if (typeof v !== 'string') {
+      return new AlanError(`Expected a string value for key ${k}`);
+    }
+    out.set(k, new Str(v));
+  }
+  return out;
+}
+
+// Numeric arrays are serialized as little-endian bytes, the same layout the Rust side uses
+function asBytes(a, width, set) {
+  let v = new DataView(new ArrayBuffer(a.length * width));
+  for (let i = 0; i < a.length; i++) {
+    set(v, i * width, a[i].val);
+  }
+  return [...new Uint8Array(v.buffer)].map((b) => new U8(b));
+}
+
+function fromBytes(b, width, get, Kind) {
+  if (b.length % width !== 0) {
+    return new AlanError(`The byte array length ${b.length} is not a multiple of ${width}`);
+  }
+  let v = new DataView(new Uint8Array(b.map((x) => x.val)).buffer);
+  let out = [];
+  for (let i = 0; i < b.length; i += width) {
+    out.push(new Kind(get(v, i)));
+  }
+  return out;
+}
+
+export function asBytesF32(a) {
+  return asBytes(a, 4, (v, o, n) => v.setFloat32(o, n, true));
+}
+
+export function fromBytesF32(b) {
+  return fromBytes(b, 4, (v, o) => v.getFloat32(o, true), F32);
+}
+
+export function asBytesF64(a) {
+  return asBytes(a, 8, (v, o, n) => v.setFloat64(o, n, true));
+}
+
+export function fromBytesF64(b) {
+  return fromBytes(b, 8, (v, o) => v.getFloat64(o, true), F64);
+}
+
+export function asBytesI64(a) {
+  return asBytes(a, 8, (v, o, n) => v.setBigInt64(o, n, true));
+}
+
+export function fromBytesI64(b) {
+  return fromBytes(b, 8, (v, o) => v.getBigInt64(o, true), I64);
+}
+
+export async function sha256hex(data) {
+  let digest = await crypto.subtle.digest('SHA-256', new Uint8Array(data.map((b) => b.val)));
+  return new Str([...new Uint8Array(digest)].map((b) => b.toString(16).padStart(2, '0')).join(''));
+}
+
+let crc32Table = null;
+
+export function crc32(data) {
+  if (crc32Table === null) {
+    crc32Table = new Uint32Array(256);
+    for (let i = 0; i < 256; i++) {
+      let c = i;
+      for (let j = 0; j < 8; j++) {
+        c = c & 1 ? 0xedb88320 ^ (c >>> 1) : c >>> 1;
+      }
+      crc32Table[i] = c;
+    }
+  }
+  let crc = 0xffffffff;
+  for (let b of data) {
+    crc = crc32Table[(crc ^ b.val) & 0xff] ^ (crc >>> 8);
+  }
+  return new I64((crc ^ 0xffffffff) >>> 0);
+}
+
+export function hash(a) {
+  // FNV-1a over the same bytes the Rust `Hash` implementations write, so the results match
+  let h = 0xcbf29ce484222325n;
+  let write = (v, bytes) => {
+    for (let i = 0; i < bytes; i++) {
+      h ^= (v >> BigInt(8 * i)) & 0xffn;
+      h = (h * 0x100000001b3n) & 0xffffffffffffffffn;
+    }
+  };
+  write(BigInt(a.length), 8);
+  for (let v of a) {
+    if (v instanceof Str) {
+      for (let b of new TextEncoder().encode(v.val)) {
+        write(BigInt(b), 1);
+      }
+      write(0xffn, 1);
+    } else if (v instanceof Bool) {
+      write(v.val ? 1n : 0n, 1);
+    } else {
+      write(BigInt.asUintN(v.bits, BigInt(v.val)), v.bits / 8);
+    }
+  }
+  return new I64(BigInt.asIntN(64, h));
+}
+
 export class GPU {
   constructor(adapter, device, queue) {
     this.adapter = adapter;
@@ -756,29 +1103,58 @@ export class GPU {
   }
 
   static async init(adapters) {
+    // Request every feature and the full limits of each adapter, since `requestDevice` otherwise
+    // defaults to a really small set of features and limits
+    return await GPU.initWith(adapters, null, null);
+  }
+
+  // Only the specified features and limits are required, though each adapter's own features or
+  // limits are used when they are `null`
+  static async initWith(adapters, features, limits) {
     let out = [];
+    let skipped = [];
+    if (adapters.length === 0) {
+      skipped.push("No WebGPU-compliant GPUs were found");
+    }
     for (let adapter of adapters) {
-      let features = adapter.features;
-      let limits = adapter.limits;
       let info = adapter.info;
-      let device = await adapter.requestDevice({
-        label: `${info.device} on ${info.architecture}`,
-        // If I don't pass these through, it defaults to a really small set of features and limits
-        requiredFeatures: features,
-        requiredLimits: limits,
-      });
-      out.push(new GPU(adapter, device, device.queue));
+      let name = `${info.device} on ${info.architecture}`;
+      let missing = [...(features ?? [])].filter((f) => !adapter.features.has(f));
+      if (missing.length > 0) {
+        skipped.push(`${name} does not support the required features ${missing.join(", ")}`);
+        continue;
+      }
+      // `shader-f16` is also requested when it's supported, since the `f16` buffers depend on it
+      let optional = features !== null && adapter.features.has("shader-f16") ? ["shader-f16"] : [];
+      // Unsupported limits are rejected by `requestDevice` itself
+      try {
+        let device = await adapter.requestDevice({
+          label: name,
+          requiredFeatures: features === null ? adapter.features : [...features, ...optional],
+          requiredLimits: limits ?? adapter.limits,
+        });
+        out.push(new GPU(adapter, device, device.queue));
+      } catch (e) {
+        skipped.push(`${name} could not be initialized: ${e.message}`);
+      }
     }
-    return out;
+    return [out, skipped];
   }
 }
 
 let GPUS = null;
+let GPU_REPORT = [];
 
-export async function gpu() {
+async function initGpus() {
   if (GPUS === null) {
-    GPUS = await GPU.init(await GPU.list());
+    // Requesting every feature an adapter offers fails on some drivers, so none are required, but
+    // the adapter's own limits are kept so buffers and workgroups aren't capped at the defaults
+    [GPUS, GPU_REPORT] = await GPU.initWith(await GPU.list(), [], null);
   }
+}
+
+export async function gpu() {
+  await initGpus();
   if (GPUS.length > 0) {
     return GPUS[0];
   } else {
@@ -786,6 +1162,21 @@ export async function gpu() {
   }
 }
 
+export async function gpuAvailable() {
+  await initGpus();
+  return new Bool(GPUS.length > 0);
+}
+
+export async function gpuInitReport() {
+  await initGpus();
+  return GPU_REPORT.map((r) => new Str(r));
+}
+
+export async function gpuInitReportWith(features) {
+  let [_, report] = await GPU.initWith(await GPU.list(), features.map((f) => f.val.toLowerCase().replaceAll("_", "-")), null);
+  return report.map((r) => new Str(r));
+}
+
 export async function createBufferInit(usage, vals) {
   let g = await gpu();
   let b = await g.device.createBuffer({
@@ -804,6 +1195,94 @@ export async function createBufferInit(usage, vals) {
   return b;
 }
 
+// Javascript does not (yet) have a half-precision float type, so conversion to and from the bit
+// pattern is done by hand
+function f32ToF16Bits(v) {
+  let bits = new Uint32Array(new Float32Array([v]).buffer)[0];
+  let sign = (bits >>> 16) & 0x8000;
+  let exp = ((bits >>> 23) & 0xff) - 127 + 15;
+  let mantissa = bits & 0x7fffff;
+  if (exp >= 31) {
+    // Too large for a half, infinity, or NaN
+    return sign | 0x7c00 | (((bits >>> 23) & 0xff) === 0xff && mantissa ? 0x200 : 0);
+  }
+  if (exp <= 0) {
+    // Subnormal, or too small and flushed to zero
+    if (exp < -10) {
+      return sign;
+    }
+    mantissa |= 0x800000;
+    let shift = 14 - exp;
+    let half = mantissa >>> shift;
+    let rem = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    if (rem > halfway || (rem === halfway && (half & 1))) {
+      half++;
+    }
+    return sign | half;
+  }
+  // Round to nearest even, letting a carry out of the mantissa bump the exponent
+  let half = sign | (exp << 10) | (mantissa >>> 13);
+  let rem = mantissa & 0x1fff;
+  if (rem > 0x1000 || (rem === 0x1000 && (half & 1))) {
+    half++;
+  }
+  return half;
+}
+function f16BitsToF32(h) {
+  let sign = h & 0x8000 ? -1 : 1;
+  let exp = (h >>> 10) & 0x1f;
+  let mantissa = h & 0x3ff;
+  if (exp === 0) {
+    return sign * mantissa * 2 ** -24;
+  } else if (exp === 31) {
+    return mantissa ? NaN : sign * Infinity;
+  } else {
+    return sign * (1 + mantissa / 1024) * 2 ** (exp - 15);
+  }
+}
+
+export async function createBufferInitF16(usage, vals) {
+  let g = await gpu();
+  if (!g.device.features.has("shader-f16")) {
+    return new AlanError("This GPU does not support f16 shaders");
+  }
+  let b = await g.device.createBuffer({
+    mappedAtCreation: true,
+    size: Math.ceil(vals.length / 2) * 4,
+    usage,
+    label: `buffer_${uuidv4().replaceAll('-', '_')}`,
+  });
+  let v = new Uint16Array(b.getMappedRange());
+  for (let i = 0; i < vals.length; i++) {
+    v[i] = f32ToF16Bits(vals[i].valueOf());
+  }
+  b.unmap();
+  b.ValKind = { bits: 16 };
+  // An odd number of values is padded with a trailing zero that isn't counted in the length
+  b.len = vals.length;
+  return b;
+}
+
+export async function createBufferInitBytes(usage, bytes) {
+  let g = await gpu();
+  let b = await g.device.createBuffer({
+    mappedAtCreation: true,
+    size: Math.max(Math.ceil(bytes.length / 4), 1) * 4,
+    usage,
+    label: `buffer_${uuidv4().replaceAll('-', '_')}`,
+  });
+  let v = new Uint8Array(b.getMappedRange());
+  for (let i = 0; i < bytes.length; i++) {
+    v[i] = bytes[i].valueOf();
+  }
+  b.unmap();
+  b.ValKind = { bits: 8 };
+  // The padding at the end isn't counted in the length
+  b.len = bytes.length;
+  return b;
+}
+
 export async function createEmptyBuffer(usage, size, ValKind) {
   let g = await gpu();
   let b = await g.device.createBuffer({
@@ -827,8 +1306,12 @@ export function storageBufferType() {
   return GPUBufferUsage.STORAGE | GPUBufferUsage.COPY_DST | GPUBufferUsage.COPY_SRC;
 }
 
+export function indirectBufferType() {
+  return GPUBufferUsage.STORAGE | GPUBufferUsage.INDIRECT | GPUBufferUsage.COPY_DST | GPUBufferUsage.COPY_SRC;
+}
+
 export function bufferlen(b) {
-    return new I64(b.size / ((b?.ValKind?.bits ?? 32) / 8));
+    return new I64(b.len ?? b.size / ((b?.ValKind?.bits ?? 32) / 8));
 }
 
 export function bufferid(b) {
@@ -841,9 +1324,23 @@ export class GPGPU {
     this.entrypoint = entrypoint ?? "main";
     this.buffers = buffers;
     this.workgroupSizes = workgroupSizes;
+    this.indirect = undefined;
     this.module = undefined;
     this.computePipeline = undefined;
   }
+
+  setBuffers(buffers) {
+    this.buffers = buffers;
+  }
+}
+
+export function newIndirectGPGPU(source, buffers, indirect) {
+  if (indirect.size < 12) {
+    return new AlanError("The indirect dispatch buffer must hold at least three u32 values (12 bytes)");
+  }
+  let gg = new GPGPU(source, buffers, [1, 1, 1]);
+  gg.indirect = indirect;
+  return gg;
 }
 
 export async function gpuRun(gg) {
@@ -883,11 +1380,15 @@ export async function gpuRun(gg) {
     });
     cpass.setBindGroup(i, bindGroup);
   }
-  cpass.dispatchWorkgroups(
-    gg.workgroupSizes[0].valueOf(),
-    (gg.workgroupSizes[1] ?? 1).valueOf(),
-    (gg.workgroupSizes[2] ?? 1).valueOf()
-  );
+  if (gg.indirect) {
+    cpass.dispatchWorkgroupsIndirect(gg.indirect, 0);
+  } else {
+    cpass.dispatchWorkgroups(
+      gg.workgroupSizes[0].valueOf(),
+      (gg.workgroupSizes[1] ?? 1).valueOf(),
+      (gg.workgroupSizes[2] ?? 1).valueOf()
+    );
+  }
   cpass.end();
   g.queue.submit([encoder.finish()]);
 }
@@ -930,16 +1431,25 @@ export async function gpuRunList(ggs) {
       });
       cpass.setBindGroup(i, bindGroup);
     }
-    cpass.dispatchWorkgroups(
-      gg.workgroupSizes[0].valueOf(),
-      (gg.workgroupSizes[1] ?? 1).valueOf(),
-      (gg.workgroupSizes[2] ?? 1).valueOf()
-    );
+    if (gg.indirect) {
+      cpass.dispatchWorkgroupsIndirect(gg.indirect, 0);
+    } else {
+      cpass.dispatchWorkgroups(
+        gg.workgroupSizes[0].valueOf(),
+        (gg.workgroupSizes[1] ?? 1).valueOf(),
+        (gg.workgroupSizes[2] ?? 1).valueOf()
+      );
+    }
     cpass.end();
   }
   g.queue.submit([encoder.finish()]);
 }
 
+export async function gpuWaitIdle() {
+  let g = await gpu();
+  await g.queue.onSubmittedWorkDone();
+}
+
 export async function readBuffer(b) {
   let g = await gpu();
   await g.queue.onSubmittedWorkDone(); // Don't try to read until you're sure it's safe to
@@ -959,6 +1469,43 @@ export async function readBuffer(b) {
   return out;
 }
 
+export async function readBufferF16(b) {
+  let g = await gpu();
+  await g.queue.onSubmittedWorkDone();
+  let tempBuffer = await createEmptyBuffer(mapReadBufferType(), b.size / 4);
+  let encoder = g.device.createCommandEncoder();
+  encoder.copyBufferToBuffer(b, 0, tempBuffer, 0, b.size);
+  g.queue.submit([encoder.finish()]);
+  await tempBuffer.mapAsync(GPUMapMode.READ);
+  let vals = new Uint16Array(tempBuffer.getMappedRange(0, b.size));
+  let out = [];
+  for (let i = 0; i < (b.len ?? vals.length); i++) {
+    out[i] = new F32(f16BitsToF32(vals[i]));
+  }
+  tempBuffer.unmap();
+  tempBuffer.destroy();
+  return out;
+}
+
+export async function readBufferBytes(b) {
+  let g = await gpu();
+  await g.queue.onSubmittedWorkDone();
+  let tempBuffer = await createEmptyBuffer(mapReadBufferType(), b.size / 4);
+  let encoder = g.device.createCommandEncoder();
+  encoder.copyBufferToBuffer(b, 0, tempBuffer, 0, b.size);
+  g.queue.submit([encoder.finish()]);
+  await tempBuffer.mapAsync(GPUMapMode.READ);
+  let vals = new Uint8Array(tempBuffer.getMappedRange(0, b.size));
+  let out = [];
+  // The padding at the end isn't part of the buffer
+  for (let i = 0; i < (b.len ?? vals.length); i++) {
+    out[i] = new U8(vals[i]);
+  }
+  tempBuffer.unmap();
+  tempBuffer.destroy();
+  return out;
+}
+
 export async function replaceBuffer(b, v) {
   if (v.length != bufferlen(b)) {
     return new AlanError("The input array is not the same size as the buffer");
@@ -971,6 +1518,16 @@ export async function replaceBuffer(b, v) {
   tempBuffer.destroy();
 }
 
+export async function copyBuffer(src, dst) {
+  if (src.size != dst.size) {
+    return new AlanError("The source and destination buffers are not the same size");
+  }
+  let g = await gpu();
+  let encoder = g.device.createCommandEncoder();
+  encoder.copyBufferToBuffer(src, 0, dst, 0, src.size);
+  g.queue.submit([encoder.finish()]);
+}
+
 /// Window-related types and functions
 
 export function contextWidth(context) {
@@ -1001,6 +1558,41 @@ export function contextMouseY(context) {
   return context.mouseY;
 }
 
+// Buttons are numbered like the DOM `MouseEvent.button`, which the Rust side matches
+export function contextPressMouseButton(context, button) {
+  if (!context.mouseDown.has(button)) {
+    context.mouseDown.add(button);
+    context.mouseClicked.add(button);
+  }
+}
+
+export function contextReleaseMouseButton(context, button) {
+  context.mouseDown.delete(button);
+}
+
+export function contextIsMouseDown(context, button) {
+  return new Bool(context.mouseDown.has(button.valueOf()));
+}
+
+export function contextClickedThisFrame(context, button) {
+  return new Bool(context.mouseClicked.has(button.valueOf()));
+}
+
+// Scrolling is measured in pixels, with positive values scrolling down, and is added together
+// between frames
+export function contextScroll(context, delta) {
+  context.scroll += delta;
+}
+
+export function contextScrollDelta(context) {
+  return new F32(context.scroll);
+}
+
+export function contextEndFrame(context) {
+  context.mouseClicked.clear();
+  context.scroll = 0;
+}
+
 export function contextCursorVisible(context) {
   context.cursorVisible = true;
 }
@@ -1017,6 +1609,47 @@ export function contextOpaque(context) {
   context.transparent = false;
 }
 
+export function contextInitialSize(context, width, height) {
+  context.initialWidth = width.val;
+  context.initialHeight = height.val;
+}
+
+export function contextResizable(context) {
+  context.resizable = true;
+}
+
+export function contextFixedSize(context) {
+  context.resizable = false;
+}
+
+export function contextAuxBuffers(context, count) {
+  context.auxBuffers = count.val;
+}
+
+export function contextSurfaceFormat(context, format) {
+  context.surfaceFormat = format.val;
+}
+
+export function selectSurfaceFormat(context, supported, preferred) {
+  if (context.surfaceFormat === undefined) {
+    return preferred;
+  }
+  if (!supported.includes(context.surfaceFormat)) {
+    return new AlanError(`Surface format ${context.surfaceFormat} is not supported, the supported formats are ${supported.join(', ')}`);
+  }
+  return context.surfaceFormat;
+}
+
+export function windowAttributes(context) {
+  // Without an initial size, the canvas takes up the whole window
+  return {
+    width: context.initialWidth ?? window.innerWidth,
+    height: context.initialHeight ?? window.innerHeight,
+    fillWindow: context.initialWidth === undefined,
+    resizable: context.resizable ?? true,
+  };
+}
+
 export function contextRuntime(context) {
   return f32AsU32(new F32((performance.now() - context.start) / 1000.0));
 }
@@ -1029,6 +1662,19 @@ export function frameFramebuffer(frame) {
   return frame.framebuffer;
 }
 
+export function frameAux(frame, i) {
+  let idx = Number(i.val);
+  if (idx < 0 || idx >= frame.aux.length) {
+    return new AlanError(`Auxiliary buffer ${idx} does not exist, only ${frame.aux.length} were requested`);
+  }
+  return frame.aux[idx];
+}
+
+// There is no file system to save a frame to in the browser
+export async function renderFrame(initialContextFn, contextFn, gpgpuShaderFn, path) {
+  return new AlanError(`Cannot save a frame to ${path.val}, rendering to a file is only supported natively`);
+}
+
 export async function runWindow(initialContextFn, contextFn, gpgpuShaderFn) {
   // None of this can run before `document.body` exists, so let's wait for that
   await new Promise((r) => document.addEventListener("DOMContentLoaded", () => r()));
@@ -1038,13 +1684,22 @@ export async function runWindow(initialContextFn, contextFn, gpgpuShaderFn) {
     bufferWidth: undefined,
     mouseX: undefined,
     mouseY: undefined,
+    mouseDown: new Set(),
+    mouseClicked: new Set(),
+    scroll: 0,
     cursorVisible: true,
     transparent: false,
+    initialWidth: undefined,
+    initialHeight: undefined,
+    resizable: true,
+    auxBuffers: 0,
+    surfaceFormat: undefined,
   };
   await initialContextFn(context);
   context.start = performance.now();
   // If the `initialContextFn` doesn't attach a canvas, we make one to take up the whole window
   if (!context.canvas) {
+    let attrs = windowAttributes(context);
     let canvas = document.createElement('canvas');
     canvas.setAttribute('id', 'AlanWindow');
     document.body.appendChild(canvas);
@@ -1052,14 +1707,16 @@ export async function runWindow(initialContextFn, contextFn, gpgpuShaderFn) {
     canvas.style['position'] = 'absolute';
     canvas.style['left'] = '0px';
     canvas.style['top'] = '0px';
-    canvas.style['width'] = '100%';
-    canvas.style['height'] = '100%';
-    canvas.width = window.innerWidth;
-    canvas.height = window.innerHeight;
-    document.body.addEventListener("resize", () => {
-      canvas.width = window.innerWidth;
-      canvas.height = window.innerHeight;
-    });
+    canvas.style['width'] = attrs.fillWindow ? '100%' : `${attrs.width}px`;
+    canvas.style['height'] = attrs.fillWindow ? '100%' : `${attrs.height}px`;
+    canvas.width = attrs.width;
+    canvas.height = attrs.height;
+    if (attrs.fillWindow && attrs.resizable) {
+      document.body.addEventListener("resize", () => {
+        canvas.width = window.innerWidth;
+        canvas.height = window.innerHeight;
+      });
+    }
     if (!context.cursorVisible) {
       canvas.style['cursor'] = 'none';
     }
@@ -1071,16 +1728,39 @@ export async function runWindow(initialContextFn, contextFn, gpgpuShaderFn) {
       context.mouseY = new U32(event.offsetY);
     }
   });
+  context.canvas.addEventListener("mousedown", (event) => {
+    contextPressMouseButton(context, event.button);
+  });
+  context.canvas.addEventListener("mouseup", (event) => {
+    contextReleaseMouseButton(context, event.button);
+  });
+  context.canvas.addEventListener("wheel", (event) => {
+    switch (event.deltaMode) {
+    case WheelEvent.DOM_DELTA_LINE:
+      contextScroll(context, event.deltaY * 16);
+      break;
+    case WheelEvent.DOM_DELTA_PAGE:
+      contextScroll(context, event.deltaY * context.canvas.height);
+      break;
+    default:
+      contextScroll(context, event.deltaY);
+    }
+  });
   let surface = context.canvas.getContext('webgpu');
   let adapter = await navigator.gpu.requestAdapter();
   let device = await adapter.requestDevice();
   let queue = device.queue;
+  // These are the only formats a WebGPU canvas can be configured with
+  let format = selectSurfaceFormat(context, ['bgra8unorm', 'rgba8unorm', 'rgba16float'], 'bgra8unorm');
+  if (format instanceof AlanError) {
+    return format;
+  }
   surface.configure({
     device,
-    format: 'bgra8unorm',
+    format,
     alphaMode: context.transparent ? 'premultiplied' : 'opaque',
     usage: GPUTextureUsage.COPY_DST | GPUTextureUsage.RENDER_ATTACHMENT,
-    viewFormats: ['bgra8unorm'],
+    viewFormats: [format],
   });
   let contextBuffer = await device.createBuffer({
     size: 16,
@@ -1099,7 +1779,24 @@ export async function runWindow(initialContextFn, contextFn, gpgpuShaderFn) {
     label: `buffer_${uuidv4().replaceAll('-', '_')}`,
   });
   buffer.ValKind = U32;
-  let gpgpuShaders = await gpgpuShaderFn({ context: contextBuffer, framebuffer: buffer });
+  let newWindowBuffer = async () => {
+    let b = await device.createBuffer({
+      size: bufferSize,
+      usage: storageBufferType(),
+      label: `buffer_${uuidv4().replaceAll('-', '_')}`,
+    });
+    b.ValKind = U32;
+    return b;
+  };
+  let auxBuffers = [];
+  for (let i = 0; i < context.auxBuffers; i++) {
+    auxBuffers.push(await newWindowBuffer());
+  }
+  let gpgpuShaders = await gpgpuShaderFn({
+    context: contextBuffer,
+    framebuffer: buffer,
+    aux: auxBuffers,
+  });
   let redraw = async function() {
     // First resize things if necessary
     if (width !== context.canvas.width || height !== context.canvas.height) {
@@ -1108,36 +1805,33 @@ export async function runWindow(initialContextFn, contextFn, gpgpuShaderFn) {
       context.bufferWidth = (4 * width) % 256 === 0 ? 4 * width : 4 * width + (256 - ((4 * width) % 256));
       bufferHeight = height;
       bufferSize = context.bufferWidth * bufferHeight;
-      let oldBufferId = buffer.label;
-      let newBuffer = await device.createBuffer({
-        size: bufferSize,
-        usage: storageBufferType(),
-        label: `buffer_${uuidv4().replaceAll('-', '_')}`,
-      });
-      newBuffer.ValKind = U32;
+      // The auxiliary buffers are always the same size as the framebuffer
+      let oldBuffers = [buffer, ...auxBuffers];
+      let newBuffers = [];
+      for (let i = 0; i < oldBuffers.length; i++) {
+        newBuffers.push(await newWindowBuffer());
+      }
       for (let shader of gpgpuShaders) {
         for (let group of shader.buffers) {
-          let idx = undefined;
           for (let i = 0; i < group.length; i++) {
-            let buffer = group[i];
-            if (buffer.label == oldBufferId) {
-              idx = i;
-              break;
+            let idx = oldBuffers.findIndex((b) => b.label === group[i].label);
+            if (idx !== -1) {
+              group[i] = newBuffers[idx];
             }
           }
-          if (typeof(idx) !== 'undefined') {
-            group[idx] = newBuffer;
-          }
         }
       }
-      buffer.destroy();
-      buffer = newBuffer;
+      for (let b of oldBuffers) {
+        b.destroy();
+      }
+      [buffer, ...auxBuffers] = newBuffers;
     }
     // Now, actually start drawing
     let oldContextBufferId = contextBuffer.label;
     let frame = surface.getCurrentTexture();
     let encoder = device.createCommandEncoder();
     let contextArray = await contextFn(context);
+    contextEndFrame(context);
     let newContextBuffer = await device.createBuffer({
       mappedAtCreation: true,
       size: contextArray.length * 4,
@@ -465,3 +465,76 @@ assert.equal(new alanStd.F64(-1.5).roundTiesEven(), -2.0, "roundTiesEvenF64 -1.5
 assert.equal(new alanStd.F64(-1.75).roundTiesEven(), -2.0, "roundTiesEvenF64 -1.75 == -2.0");
 assert.equal(new alanStd.F64(-2.5).roundTiesEven(), -2.0, "roundTiesEvenF64 -2.5 == -2.0");
 assert.equal(new alanStd.F64(-2.75).roundTiesEven(), -3.0, "roundTiesEvenF64 -2.75 == -3.0");
+
+{
+  let a = alanStd.seedRng(new alanStd.I64(0n));
+  let b = alanStd.seedRng(new alanStd.I64(0n));
+  assert.strictEqual(alanStd.nextI64(a).val, -2_152_535_657_050_944_081n, "nextI64 seed 0 matches SplitMix64");
+  alanStd.nextI64(b);
+  assert.strictEqual(alanStd.nextI64(a).val, alanStd.nextI64(b).val, "nextI64 same seed same sequence");
+  let f = alanStd.nextF64(a).val;
+  assert(f >= 0 && f < 1, "nextF64 in [0, 1)");
+}
+
+{
+  let d = new Map([["2", new alanStd.Str("b")], ["1", new alanStd.Str("a")]]);
+  assert.strictEqual(alanStd.toJson(d).val, '{"2":"b","1":"a"}', "toJson preserves key order");
+  assert.strictEqual(alanStd.parseJson(new alanStd.Str('{"a":"b"}')).get("a").val, "b", "parseJson object");
+  assert(alanStd.parseJson(new alanStd.Str('{"a":')) instanceof alanStd.AlanError, "parseJson malformed");
+  assert(alanStd.parseJson(new alanStd.Str('{"a":1}')) instanceof alanStd.AlanError, "parseJson non-string value");
+}
+
+{
+  let abc = [97, 98, 99].map((b) => new alanStd.U8(b));
+  assert.strictEqual((await alanStd.sha256hex([])).val, "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855", "sha256hex empty");
+  assert.strictEqual((await alanStd.sha256hex(abc)).val, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad", "sha256hex abc");
+  assert.strictEqual(alanStd.crc32([]).val, 0n, "crc32 empty");
+  assert.strictEqual(alanStd.crc32(abc).val, 891_568_578n, "crc32 abc");
+}
+
+{
+  let context = { mouseDown: new Set(), mouseClicked: new Set() };
+  let left = new alanStd.U32(0);
+  assert.strictEqual(alanStd.contextIsMouseDown(context, left).val, false, "mouse starts up");
+  alanStd.contextPressMouseButton(context, 0);
+  assert.strictEqual(alanStd.contextIsMouseDown(context, left).val, true, "mouse down after press");
+  assert.strictEqual(alanStd.contextClickedThisFrame(context, left).val, true, "click seen the frame it happened");
+  assert.strictEqual(alanStd.contextIsMouseDown(context, new alanStd.U32(2)).val, false, "other buttons stay up");
+  alanStd.contextEndFrame(context);
+  assert.strictEqual(alanStd.contextIsMouseDown(context, left).val, true, "held button stays down across frames");
+  assert.strictEqual(alanStd.contextClickedThisFrame(context, left).val, false, "click not seen the next frame");
+  alanStd.contextReleaseMouseButton(context, 0);
+  assert.strictEqual(alanStd.contextIsMouseDown(context, left).val, false, "mouse up after release");
+}
+
+{
+  let context = { mouseDown: new Set(), mouseClicked: new Set(), scroll: 0 };
+  alanStd.contextScroll(context, 48);
+  alanStd.contextScroll(context, -16);
+  assert.strictEqual(alanStd.contextScrollDelta(context).val, 32, "scroll accumulates between frames");
+  alanStd.contextEndFrame(context);
+  assert.strictEqual(alanStd.contextScrollDelta(context).val, 0, "scroll resets each frame");
+}
+
+{
+  let context = { resizable: true };
+  alanStd.contextInitialSize(context, new alanStd.U32(640), new alanStd.U32(480));
+  alanStd.contextFixedSize(context);
+  let attrs = alanStd.windowAttributes(context);
+  assert.strictEqual(attrs.width, 640, "initial width applied");
+  assert.strictEqual(attrs.height, 480, "initial height applied");
+  assert.strictEqual(attrs.fillWindow, false, "sized windows don't fill the page");
+  assert.strictEqual(attrs.resizable, false, "fixed size windows aren't resizable");
+  alanStd.contextResizable(context);
+  assert.strictEqual(alanStd.windowAttributes(context).resizable, true, "resizable again");
+}
+
+{
+  let context = {};
+  let supported = ['bgra8unorm', 'rgba8unorm', 'rgba16float'];
+  assert.strictEqual(alanStd.selectSurfaceFormat(context, supported, 'bgra8unorm'), 'bgra8unorm', "default surface format");
+  alanStd.contextSurfaceFormat(context, new alanStd.Str('rgba16float'));
+  assert.strictEqual(alanStd.selectSurfaceFormat(context, supported, 'bgra8unorm'), 'rgba16float', "requested surface format selected");
+  alanStd.contextSurfaceFormat(context, new alanStd.Str('r8unorm'));
+  assert(alanStd.selectSurfaceFormat(context, supported, 'bgra8unorm') instanceof alanStd.AlanError, "unsupported surface format");
+}
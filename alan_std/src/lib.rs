@@ -47,6 +47,14 @@ impl From<String> for AlanError {
     }
 }
 
+impl From<std::io::Error> for AlanError {
+    fn from(e: std::io::Error) -> AlanError {
+        AlanError {
+            message: e.to_string(),
+        }
+    }
+}
+
 /// String-related functions
 
 /// Converts anything that implements ToString into a string. Needed to convert all errors into
@@ -67,6 +75,29 @@ pub fn splitstring(a: &String, b: &String) -> Vec<String> {
     }
 }
 
+/// `linesstring` splits the string into lines, accepting both `\n` and `\r\n` line endings. A
+/// trailing line ending does not produce a final empty line
+#[inline(always)]
+pub fn linesstring(a: &String) -> Vec<String> {
+    a.lines().map(|v| v.to_string()).collect::<Vec<String>>()
+}
+
+/// `splitoncestring` splits the string around the first occurrence of the separator, if there is
+/// one, leaving any later occurrences in the second part
+#[inline(always)]
+pub fn splitoncestring(a: &String, sep: &String) -> Option<(String, String)> {
+    a.split_once(sep.as_str())
+        .map(|(l, r)| (l.to_string(), r.to_string()))
+}
+
+/// `rsplitoncestring` splits the string around the last occurrence of the separator, if there is
+/// one, leaving any earlier occurrences in the first part
+#[inline(always)]
+pub fn rsplitoncestring(a: &String, sep: &String) -> Option<(String, String)> {
+    a.rsplit_once(sep.as_str())
+        .map(|(l, r)| (l.to_string(), r.to_string()))
+}
+
 /// `getstring` returns the character at the specified index (TODO: What is a "character" in Alan?)
 #[inline(always)]
 pub fn getstring(a: &String, i: &i64) -> Result<String, AlanError> {
@@ -111,12 +142,74 @@ pub fn getarray<T: Clone>(a: &Vec<T>, i: &i64) -> Option<T> {
     a.get(*i as usize).cloned()
 }
 
+/// `firstarray` returns the first value of the array, if there is one
+#[inline(always)]
+pub fn firstarray<T: Clone>(a: &Vec<T>) -> Option<T> {
+    a.first().cloned()
+}
+
+/// `lastarray` returns the last value of the array, if there is one
+#[inline(always)]
+pub fn lastarray<T: Clone>(a: &Vec<T>) -> Option<T> {
+    a.last().cloned()
+}
+
 /// `filled` returns a filled Vec<V> of the provided value for the provided size
 #[inline(always)]
 pub fn filled<V: std::clone::Clone>(i: &V, l: &i64) -> Vec<V> {
     vec![i.clone(); *l as usize]
 }
 
+/// `zerosf64` returns a Vec<f64> of zeros for the provided size
+#[inline(always)]
+pub fn zerosf64(l: &i64) -> Vec<f64> {
+    filled(&0.0, l)
+}
+
+/// `onesf64` returns a Vec<f64> of ones for the provided size
+#[inline(always)]
+pub fn onesf64(l: &i64) -> Vec<f64> {
+    filled(&1.0, l)
+}
+
+/// `rangearray_i64` returns the arithmetic sequence from `start` up to (but not including) `end`,
+/// counting by `step`, which may be negative to count down. It fails if the step is zero
+#[inline(always)]
+pub fn rangearray_i64(start: &i64, end: &i64, step: &i64) -> Result<Vec<i64>, AlanError> {
+    if *step == 0 {
+        return Err("Range step must not be zero".into());
+    }
+    let mut out = Vec::new();
+    let mut i = *start;
+    while (*step > 0 && i < *end) || (*step < 0 && i > *end) {
+        out.push(i);
+        i = match i.checked_add(*step) {
+            Some(i) => i,
+            None => break,
+        };
+    }
+    Ok(out)
+}
+
+/// `linspacef64` returns `count` evenly-spaced values from `start` to `end`, inclusive. Each value
+/// is computed from its index rather than accumulated, and the last is exactly `end`. It fails if
+/// the count is less than one
+#[inline(always)]
+pub fn linspacef64(start: &f64, end: &f64, count: &i64) -> Result<Vec<f64>, AlanError> {
+    if *count < 1 {
+        return Err(format!("Linspace count must be at least 1, got {}", count).into());
+    }
+    if *count == 1 {
+        return Ok(vec![*start]);
+    }
+    let steps = (*count - 1) as f64;
+    let mut out = (0..*count)
+        .map(|i| start + (end - start) * (i as f64 / steps))
+        .collect::<Vec<f64>>();
+    out[*count as usize - 1] = *end;
+    Ok(out)
+}
+
 /// `map_onearg` runs the provided single-argument function on each element of the vector,
 /// returning a new vector
 #[inline(always)]
@@ -217,6 +310,27 @@ pub fn parmap_onearg<
     }
 }
 
+/// `parmap_result` runs the provided fallible function on each element of the vector in parallel,
+/// the same way `parmap_onearg` does. If any element fails, the error for the lowest index is
+/// returned, with that index included in the message.
+pub fn parmap_result<
+    A: std::marker::Sync + 'static,
+    B: std::marker::Send + std::clone::Clone + 'static,
+>(
+    v: &Vec<A>,
+    f: fn(&A) -> Result<B, AlanError>,
+) -> Result<Vec<B>, AlanError> {
+    parmap_onearg(v, f)
+        .into_iter()
+        .enumerate()
+        .map(|(i, r)| {
+            r.map_err(|e| AlanError {
+                message: format!("Element {} failed: {}", i, e.message),
+            })
+        })
+        .collect()
+}
+
 /// `filter_onearg` runs the provided single-argument function on each element of the vector,
 /// returning a new vector
 #[inline(always)]
@@ -354,6 +468,15 @@ pub fn findarray<T: std::clone::Clone>(a: &Vec<T>, mut f: impl FnMut(&T) -> bool
     return None;
 }
 
+/// `partitionpointarray` returns the index of the first value that matches the check function,
+/// or the length of the vector if none do. It uses a binary search, so the vector *must* already be
+/// partitioned, with every non-matching value before every matching value; otherwise the result
+/// is meaningless
+#[inline(always)]
+pub fn partitionpointarray<T>(a: &Vec<T>, mut f: impl FnMut(&T) -> bool) -> i64 {
+    a.partition_point(|v| !f(v)) as i64
+}
+
 /// `everyarray` returns true if every value in the vector matches the check function
 #[inline(always)]
 pub fn everyarray<T>(a: &Vec<T>, mut f: impl FnMut(&T) -> bool) -> bool {
@@ -376,6 +499,38 @@ pub fn somearray<T>(a: &Vec<T>, mut f: impl FnMut(&T) -> bool) -> bool {
     return false;
 }
 
+/// `tryallarray` collects a vector of results into a result of the vector, failing with the first
+/// error in the vector, if any
+#[inline(always)]
+pub fn tryallarray<T: std::clone::Clone>(
+    a: &Vec<Result<T, AlanError>>,
+) -> Result<Vec<T>, AlanError> {
+    a.iter().cloned().collect()
+}
+
+/// `partitionresultsarray` splits a vector of results into the successful values and the errors,
+/// each in their original order
+#[inline(always)]
+pub fn partitionresultsarray<T: std::clone::Clone>(
+    a: &Vec<Result<T, AlanError>>,
+) -> (Vec<T>, Vec<AlanError>) {
+    let mut oks = Vec::new();
+    let mut errs = Vec::new();
+    for v in a {
+        match v {
+            Ok(v) => oks.push(v.clone()),
+            Err(e) => errs.push(e.clone()),
+        }
+    }
+    (oks, errs)
+}
+
+/// `flattenoptionarray` keeps only the values that exist in a vector of optional values, in order
+#[inline(always)]
+pub fn flattenoptionarray<T: std::clone::Clone>(a: &Vec<Option<T>>) -> Vec<T> {
+    a.iter().flatten().cloned().collect()
+}
+
 /// `repeatarray` returns a new array with the original array repeated N times
 #[inline(always)]
 pub fn repeatarray<T: std::clone::Clone>(a: &Vec<T>, c: &i64) -> Vec<T> {
@@ -388,6 +543,22 @@ pub fn repeatarray<T: std::clone::Clone>(a: &Vec<T>, c: &i64) -> Vec<T> {
     out
 }
 
+/// `cyclearray` returns a new array of exactly N values by repeating the original array's values
+/// in order, cutting off the last repetition partway through if necessary. It fails if the
+/// original array is empty and there are values to fill.
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn cyclearray<T: std::clone::Clone>(a: &Vec<T>, n: &i64) -> Result<Vec<T>, AlanError> {
+    if a.is_empty() && *n > 0 {
+        return Err(format!("Cannot cycle an empty array to fill {} values", n).into());
+    }
+    Ok(a.iter()
+        .cycle()
+        .take((*n).max(0) as usize)
+        .cloned()
+        .collect())
+}
+
 /// `storearray` inserts a new value at the specified index, but fails if the index is greater than
 /// the length of the length of the array (so there would be at least one "gap" in the array in
 /// that situation)
@@ -420,6 +591,72 @@ pub fn deletearray<T: std::clone::Clone>(a: &mut Vec<T>, i: &i64) -> Result<T, A
     }
 }
 
+/// `swapremovearray` deletes a value at the specified index in constant time by moving the last
+/// value of the array into its place, so unlike `deletearray` it does *not* preserve the order of
+/// the array. It fails if the index is out-of-bounds, otherwise it returns the removed value.
+#[inline(always)]
+pub fn swapremovearray<T: std::clone::Clone>(a: &mut Vec<T>, i: &i64) -> Result<T, AlanError> {
+    match *i < 0 || (*i as usize) >= a.len() {
+        true => Err(format!(
+            "Provided array index {} is beyond the bounds of the array",
+            i
+        )
+        .into()),
+        false => Ok(a.swap_remove(*i as usize)),
+    }
+}
+
+/// `retainarray` removes every value that fails the provided predicate from the array in place,
+/// preserving the order of the values that remain. Unlike `filter_onearg` it doesn't allocate a
+/// new array, and it returns how many values were removed.
+#[inline(always)]
+pub fn retainarray<T>(a: &mut Vec<T>, mut f: impl FnMut(&T) -> bool) -> i64 {
+    let len = a.len();
+    a.retain(|v| f(v));
+    (len - a.len()) as i64
+}
+
+/// `insertmanyarray` inserts all of the provided values into the array starting at the specified
+/// index, shifting the values after it to the right. Like `storearray`, the index may be the
+/// length of the array, but is an error beyond that
+#[inline(always)]
+pub fn insertmanyarray<T: std::clone::Clone>(
+    a: &mut Vec<T>,
+    i: &i64,
+    vs: &Vec<T>,
+) -> Result<(), AlanError> {
+    if *i < 0 || (*i as usize) > a.len() {
+        return Err(format!(
+            "Provided array index {} is greater than the length of the array",
+            i
+        )
+        .into());
+    }
+    let i = *i as usize;
+    a.splice(i..i, vs.iter().cloned());
+    Ok(())
+}
+
+/// `removerangearray` removes `len` values from the array starting at the specified index and
+/// returns them as a new array. It fails if any part of the range is out-of-bounds
+#[inline(always)]
+pub fn removerangearray<T: std::clone::Clone>(
+    a: &mut Vec<T>,
+    start: &i64,
+    len: &i64,
+) -> Result<Vec<T>, AlanError> {
+    if *start < 0 || *len < 0 || start.saturating_add(*len) as usize > a.len() {
+        return Err(format!(
+            "Provided array range {}..{} is beyond the bounds of the array",
+            start,
+            start.saturating_add(*len)
+        )
+        .into());
+    }
+    let start = *start as usize;
+    Ok(a.drain(start..start + *len as usize).collect::<Vec<T>>())
+}
+
 /// `swaparray` swaps the values at the specified indicies (or fails if either index is out of
 /// bounds). It returns a Fallible void value.
 #[inline(always)]
@@ -474,210 +711,743 @@ pub fn sortarray<T>(a: &mut Vec<T>, mut sorter: impl FnMut(&T, &T) -> i8) {
     a.sort_by(|a, b| sorter(a, b).cmp(&0));
 }
 
-/// Buffer-related functions
-
-/// `getbuffer` returns the value at the given index presuming it exists
+/// `takearray` returns a new array of the first `n` values of the original array, or the entire
+/// array if `n` is larger than its length. A negative `n` is treated as zero
 #[inline(always)]
-pub fn getbuffer<T: std::clone::Clone, const S: usize>(b: &[T; S], i: &i64) -> Option<T> {
-    b.get(*i as usize).cloned()
+pub fn takearray<T: std::clone::Clone>(a: &Vec<T>, n: &i64) -> Vec<T> {
+    let n = (*n).clamp(0, a.len() as i64) as usize;
+    a[..n].to_vec()
 }
 
-/// `mapbuffer_onearg` runs the provided single-argument function on each element of the buffer,
-/// returning a new buffer
+/// `droparray` returns a new array of all but the first `n` values of the original array, which
+/// is empty if `n` is larger than its length. A negative `n` is treated as zero
 #[inline(always)]
-pub fn mapbuffer_onearg<A, const N: usize, B>(v: &[A; N], mut m: impl FnMut(&A) -> B) -> [B; N] {
-    std::array::from_fn(|i| m(&v[i]))
+pub fn droparray<T: std::clone::Clone>(a: &Vec<T>, n: &i64) -> Vec<T> {
+    let n = (*n).clamp(0, a.len() as i64) as usize;
+    a[n..].to_vec()
 }
 
-/// `mapbuffer_twoarg` runs the provided two-argument (value, index) function on each element of the
-/// buffer, returning a new buffer
+/// `takelastarray` returns a new array of the last `n` values of the original array, or the
+/// entire array if `n` is larger than its length. A negative `n` is treated as zero
 #[inline(always)]
-pub fn mapbuffer_twoarg<A, const N: usize, B: std::marker::Copy>(
-    v: &[A; N],
-    mut m: impl FnMut(&A, &i64) -> B,
-) -> [B; N] {
-    let mut out = [m(&v[0], &0); N];
-    for i in 1..N {
-        out[i] = m(&v[i], &(i as i64));
-    }
-    out
+pub fn takelastarray<T: std::clone::Clone>(a: &Vec<T>, n: &i64) -> Vec<T> {
+    let n = (*n).clamp(0, a.len() as i64) as usize;
+    a[a.len() - n..].to_vec()
 }
 
-/// `reducebuffer_sametype` runs the provided function to reduce the buffer into a singular
-/// value
+/// `droplastarray` returns a new array of all but the last `n` values of the original array,
+/// which is empty if `n` is larger than its length. A negative `n` is treated as zero
 #[inline(always)]
-pub fn reducebuffer_sametype<A: std::clone::Clone, const S: usize>(
-    b: &[A; S],
-    mut f: impl FnMut(&A, &A) -> A,
-) -> Option<A> {
-    // The built-in iter `reduce` is awkward for our use case
-    if b.len() == 0 {
-        None
-    } else if b.len() == 1 {
-        Some(b[0].clone())
-    } else {
-        let mut out = b[0].clone();
-        for i in 1..b.len() {
-            out = f(&out, &b[i]);
-        }
-        Some(out)
-    }
+pub fn droplastarray<T: std::clone::Clone>(a: &Vec<T>, n: &i64) -> Vec<T> {
+    let n = (*n).clamp(0, a.len() as i64) as usize;
+    a[..a.len() - n].to_vec()
 }
 
-/// `reducebuffer_difftype` runs the provided function and initial value to reduce the buffer into a
-/// singular value. Because an initial value is provided, it always returns at least that value
+/// `takewhilearray` returns a new array of the leading values of the original array that match the
+/// check function, stopping at the first value that does not match
 #[inline(always)]
-pub fn reducebuffer_difftype<A: std::clone::Clone, const S: usize, B: std::clone::Clone>(
-    b: &[A; S],
-    i: &B,
-    mut f: impl FnMut(&B, &A) -> B,
-) -> B {
-    let mut out = i.clone();
-    for i in 0..b.len() {
-        out = f(&out, &b[i]);
-    }
-    out
+pub fn takewhilearray<T: std::clone::Clone>(a: &Vec<T>, mut f: impl FnMut(&T) -> bool) -> Vec<T> {
+    a.iter().take_while(|v| f(v)).cloned().collect::<Vec<T>>()
 }
 
-/// `hasbuffer` returns true if the specified value exists anywhere in the array
+/// `dropwhilearray` returns a new array of the original array's values after the leading values
+/// that match the check function are skipped
 #[inline(always)]
-pub fn hasbuffer<T: std::cmp::PartialEq, const S: usize>(a: &[T; S], v: &T) -> bool {
-    for val in a {
-        if val == v {
-            return true;
-        }
-    }
-    return false;
+pub fn dropwhilearray<T: std::clone::Clone>(a: &Vec<T>, mut f: impl FnMut(&T) -> bool) -> Vec<T> {
+    a.iter().skip_while(|v| f(v)).cloned().collect::<Vec<T>>()
 }
 
-/// `hasfnbuffer` returns true if the check function returns true for any element of the array
+/// `minbyarray` returns the index and value of the smallest value in the array according to the
+/// provided comparator, using the same convention as `sortarray`. The first one wins on ties
 #[inline(always)]
-pub fn hasfnbuffer<T, const S: usize>(a: &[T; S], mut f: impl FnMut(&T) -> bool) -> bool {
-    for v in a {
-        if f(v) {
-            return true;
+pub fn minbyarray<T: std::clone::Clone>(
+    a: &Vec<T>,
+    mut cmp: impl FnMut(&T, &T) -> i8,
+) -> Option<(i64, T)> {
+    let mut out: Option<(i64, T)> = None;
+    for (i, v) in a.iter().enumerate() {
+        match &out {
+            Some((_, best)) if cmp(v, best) >= 0 => {}
+            _ => out = Some((i as i64, v.clone())),
         }
     }
-    return false;
+    out
 }
 
-/// `findbuffer` returns the first value from the buffer that matches the check function, if any
+/// `maxbyarray` returns the index and value of the largest value in the array according to the
+/// provided comparator, using the same convention as `sortarray`. The first one wins on ties
 #[inline(always)]
-pub fn findbuffer<T: std::clone::Clone, const S: usize>(
-    a: &[T; S],
-    mut f: impl FnMut(&T) -> bool,
-) -> Option<T> {
-    for v in a {
-        if f(v) {
-            return Some(v.clone());
+pub fn maxbyarray<T: std::clone::Clone>(
+    a: &Vec<T>,
+    mut cmp: impl FnMut(&T, &T) -> i8,
+) -> Option<(i64, T)> {
+    let mut out: Option<(i64, T)> = None;
+    for (i, v) in a.iter().enumerate() {
+        match &out {
+            Some((_, best)) if cmp(v, best) <= 0 => {}
+            _ => out = Some((i as i64, v.clone())),
         }
     }
-    return None;
+    out
 }
 
-/// `everybuffer` returns true if every value in the array matches the check function
+/// `minmaxarray` returns the smallest and largest values in the array according to the provided
+/// comparator, like `minbyarray` and `maxbyarray` together, but in a single pass. Values are
+/// compared in pairs, so it takes about 1.5 comparisons per value instead of 2
 #[inline(always)]
-pub fn everybuffer<T, const S: usize>(a: &[T; S], mut f: impl FnMut(&T) -> bool) -> bool {
-    for v in a {
-        if !f(v) {
-            return false;
+pub fn minmaxarray<T: std::clone::Clone>(
+    a: &Vec<T>,
+    mut cmp: impl FnMut(&T, &T) -> i8,
+) -> Option<(T, T)> {
+    let (first, rest) = a.split_first()?;
+    let mut min = first;
+    let mut max = first;
+    for pair in rest.chunks(2) {
+        // The earlier value of an equal pair is used for both, so the first one wins on ties
+        let (small, large) = match pair {
+            [x, y] => match cmp(y, x) {
+                c if c < 0 => (y, x),
+                c if c > 0 => (x, y),
+                _ => (x, x),
+            },
+            [x] => (x, x),
+            _ => unreachable!(),
+        };
+        if cmp(small, min) < 0 {
+            min = small;
+        }
+        if cmp(large, max) > 0 {
+            max = large;
         }
     }
-    return true;
+    Some((min.clone(), max.clone()))
 }
 
-/// `concatbuffer` mutates the first buffer given with the values of the other two. It depends on
-/// the provided buffer to be the right size to fit the data from both of the other buffers.
+/// `flatmaparray` runs the provided function on each element of the vector, concatenating the
+/// vectors it returns in order into a single new vector
 #[inline(always)]
-pub fn concatbuffer<T: std::clone::Clone, const S: usize, const N: usize, const O: usize>(
-    o: &mut [T; O],
-    a: &[T; S],
-    b: &[T; N],
-) {
-    for (i, v) in a.iter().chain(b).enumerate() {
-        o[i] = v.clone();
-    }
+pub fn flatmaparray<A, B: std::clone::Clone>(
+    a: &Vec<A>,
+    mut f: impl FnMut(&A) -> Vec<B>,
+) -> Vec<B> {
+    a.iter().flat_map(|v| f(v)).collect::<Vec<B>>()
 }
 
-/// `repeatbuffertoarray` returns a new array with the original buffer repeated N times
+/// `interspersearray` returns a new array with the separator value inserted between every pair of
+/// values in the original array
 #[inline(always)]
-pub fn repeatbuffertoarray<T: std::clone::Clone, const S: usize>(a: &[T; S], c: &i64) -> Vec<T> {
-    let mut out = Vec::new();
-    for _ in 0..*c {
-        for v in a {
-            out.push(v.clone());
+pub fn interspersearray<T: std::clone::Clone>(a: &Vec<T>, sep: &T) -> Vec<T> {
+    let mut out = Vec::with_capacity((a.len() * 2).saturating_sub(1));
+    for (i, v) in a.iter().enumerate() {
+        if i > 0 {
+            out.push(sep.clone());
         }
+        out.push(v.clone());
     }
     out
 }
 
-/// `storebuffer` stores the provided value in the specified index. If the index is out-of-bounds
-/// for the buffer it fails, otherwise it returns the old value.
+/// `splitatarray` returns the values before the provided index and the values from that index on
+/// as two new arrays. The index may be the length of the array, but is an error beyond that
 #[inline(always)]
-pub fn storebuffer<T: std::clone::Clone, const S: usize>(
-    a: &mut [T; S],
+pub fn splitatarray<T: std::clone::Clone>(
+    a: &Vec<T>,
     i: &i64,
-    v: &T,
-) -> Result<T, AlanError> {
-    match (*i as usize) < a.len() {
-        false => Err(format!(
-            "The provided index {} is out-of-bounds for the specified buffer",
-            i
-        )
-        .into()),
-        true => Ok(std::mem::replace(a.each_mut()[*i as usize], v.clone())),
-    }
-}
-
-/// `swapbuffer` swaps the values at the specified indicies (or fails if either index is out of
-/// bounds). It returns a Fallible void value.
-#[inline(always)]
-pub fn swapbuffer<T, const S: usize>(a: &mut [T; S], i: &i64, j: &i64) -> Result<(), AlanError> {
+) -> Result<(Vec<T>, Vec<T>), AlanError> {
     if *i < 0 {
-        return Err(format!(
-            "Provided buffer index {} is beyond the bounds of the buffer",
-            i
-        )
-        .into());
-    }
-    if *j < 0 {
-        return Err(format!(
-            "Provided buffer index {} is beyond the bounds of the buffer",
-            j
-        )
-        .into());
+        return Err(format!("Provided array index {} is negative", i).into());
     }
-    let i = *i as usize;
-    let j = *j as usize;
-    if i >= a.len() {
+    if (*i as usize) > a.len() {
         return Err(format!(
-            "Provided buffer index {} is beyond the bounds of the buffer",
+            "Provided array index {} is greater than the length of the array",
             i
         )
         .into());
     }
-    if j >= a.len() {
-        return Err(format!(
-            "Provided buffer index {} is beyond the bounds of the buffer",
-            j
-        )
-        .into());
-    }
-    if i == j {
-        return Ok(());
-    }
-    if i < j {
-        let (i_section, j_section) = a.split_at_mut(j);
-        std::mem::swap(&mut i_section[i], &mut j_section[0]);
-    } else {
-        let (j_section, i_section) = a.split_at_mut(i);
-        std::mem::swap(&mut j_section[j], &mut i_section[0]);
+    let (head, tail) = a.split_at(*i as usize);
+    Ok((head.to_vec(), tail.to_vec()))
+}
+
+/// `rotatearray` returns a new array with the values cyclically shifted to the left by `n`, or to
+/// the right for a negative `n`. Shifts larger than the array wrap around
+#[inline(always)]
+pub fn rotatearray<T: std::clone::Clone>(a: &Vec<T>, n: &i64) -> Vec<T> {
+    let mut out = a.clone();
+    if !out.is_empty() {
+        out.rotate_left(n.rem_euclid(a.len() as i64) as usize);
     }
-    Ok(())
+    out
 }
 
-/// `sortbuffer` is a thin wrapper around `sort_by` allowing for the sort decision to be done by
-/// numeric operation rather than the `Ordering` enum, which is not exposed
+/// `transposearray` turns an array of rows into an array of columns. Every row must be the same
+/// length, otherwise it is an error
+#[inline(always)]
+pub fn transposearray<T: std::clone::Clone>(a: &Vec<Vec<T>>) -> Result<Vec<Vec<T>>, AlanError> {
+    let cols = match a.first() {
+        Some(row) => row.len(),
+        None => return Ok(Vec::new()),
+    };
+    if a.iter().any(|row| row.len() != cols) {
+        return Err("Cannot transpose an array with rows of different lengths".into());
+    }
+    Ok((0..cols)
+        .map(|j| a.iter().map(|row| row[j].clone()).collect::<Vec<T>>())
+        .collect::<Vec<Vec<T>>>())
+}
+
+/// `dedupearray` returns a new array with each run of equal adjacent values collapsed into the
+/// first value of the run. Equal values that are not adjacent are kept
+#[inline(always)]
+pub fn dedupearray<T: std::clone::Clone + PartialEq>(a: &Vec<T>) -> Vec<T> {
+    let mut out = a.clone();
+    out.dedup();
+    out
+}
+
+/// `rlearray` run-length encodes the array, returning each run of equal adjacent values as the
+/// value and the length of the run
+#[inline(always)]
+pub fn rlearray<T: std::clone::Clone + PartialEq>(a: &Vec<T>) -> Vec<(T, i64)> {
+    let mut out: Vec<(T, i64)> = Vec::new();
+    for v in a.iter() {
+        match out.last_mut() {
+            Some((last, count)) if last == v => *count += 1,
+            _ => out.push((v.clone(), 1)),
+        }
+    }
+    out
+}
+
+/// `unrlearray` reverses `rlearray`, repeating each value by its count. A negative count is
+/// treated as zero
+#[inline(always)]
+pub fn unrlearray<T: std::clone::Clone>(a: &Vec<(T, i64)>) -> Vec<T> {
+    a.iter()
+        .flat_map(|(v, c)| std::iter::repeat(v.clone()).take((*c).max(0) as usize))
+        .collect::<Vec<T>>()
+}
+
+/// `frequencyarray` counts how many times each distinct value appears in the array, with the keys
+/// in the order each value was first seen
+#[inline(always)]
+pub fn frequencyarray<T: std::clone::Clone + std::hash::Hash + Eq>(
+    a: &Vec<T>,
+) -> OrderedHashMap<T, i64> {
+    let mut out = OrderedHashMap::new();
+    for v in a {
+        match out.get_mut(v) {
+            Some(count) => *count += 1,
+            None => {
+                out.insert(v.clone(), 1);
+            }
+        }
+    }
+    out
+}
+
+/// `mapreducearray` runs the map function on each element of the vector and reduces the results
+/// as it goes, without creating the intermediate vector. An empty vector returns `None`
+#[inline(always)]
+pub fn mapreducearray<A, B: std::clone::Clone>(
+    a: &Vec<A>,
+    mut mapf: impl FnMut(&A) -> B,
+    mut redf: impl FnMut(&B, &B) -> B,
+) -> Option<B> {
+    let mut out: Option<B> = None;
+    for v in a.iter() {
+        let mapped = mapf(v);
+        out = Some(match &out {
+            Some(acc) => redf(acc, &mapped),
+            None => mapped,
+        });
+    }
+    out
+}
+
+/// Vectors shorter than this are summed sequentially, as spawning the threads would cost more
+/// than it saves
+const PARSUM_THRESHOLD: usize = 1 << 16;
+
+/// `parsum` splits the vector into one chunk per thread, sums each chunk in parallel, and then
+/// sums the partial results together
+fn parsum<T: Copy + std::marker::Send + std::marker::Sync>(
+    v: &[T],
+    zero: T,
+    add: fn(T, T) -> T,
+) -> T {
+    let seq = move |s: &[T]| s.iter().fold(zero, |acc, x| add(acc, *x));
+    let par = match std::thread::available_parallelism() {
+        Ok(p) if p.get() > 1 && v.len() >= PARSUM_THRESHOLD => p.get(),
+        _ => return seq(v), // Fall back to sequential for small vectors or no parallelism
+    };
+    std::thread::scope(|s| {
+        let handles = v
+            .chunks(v.len().div_ceil(par))
+            .map(|c| s.spawn(move || seq(c)))
+            .collect::<Vec<std::thread::ScopedJoinHandle<T>>>();
+        handles
+            .into_iter()
+            .map(|h| match h.join() {
+                Err(e) => panic!("{:?}", e),
+                Ok(partial) => partial,
+            })
+            .fold(zero, add)
+    })
+}
+
+/// `parsum_f64` sums the vector in parallel. Floating point addition is not associative, so the
+/// result may differ from a sequential sum in the last few bits
+#[inline(always)]
+pub fn parsum_f64(v: &Vec<f64>) -> f64 {
+    parsum(v, 0.0, |a, b| a + b)
+}
+
+/// `parsum_i64` sums the vector in parallel, wrapping on overflow like `add` does
+#[inline(always)]
+pub fn parsum_i64(v: &Vec<i64>) -> i64 {
+    parsum(v, 0, i64::wrapping_add)
+}
+
+/// `meanf64` returns the arithmetic mean of the vector, or `None` if it is empty
+#[inline(always)]
+pub fn meanf64(a: &Vec<f64>) -> Option<f64> {
+    if a.is_empty() {
+        return None;
+    }
+    Some(a.iter().sum::<f64>() / a.len() as f64)
+}
+
+/// `medianf64` returns the middle value of the sorted vector, averaging the two middle values for
+/// an even length, or `None` if it is empty
+#[inline(always)]
+pub fn medianf64(a: &Vec<f64>) -> Option<f64> {
+    if a.is_empty() {
+        return None;
+    }
+    let mut sorted = a.clone();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    if sorted.len() % 2 == 0 {
+        Some((sorted[mid - 1] + sorted[mid]) / 2.0)
+    } else {
+        Some(sorted[mid])
+    }
+}
+
+/// `modei64` returns the most common value of the vector, the smallest of them if there is a tie,
+/// or `None` if it is empty
+#[inline(always)]
+pub fn modei64(a: &Vec<i64>) -> Option<i64> {
+    let mut counts: std::collections::HashMap<i64, i64> = std::collections::HashMap::new();
+    for v in a {
+        *counts.entry(*v).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|(v1, c1), (v2, c2)| c1.cmp(c2).then(v2.cmp(v1)))
+        .map(|(v, _)| v)
+}
+
+/// `variancef64` returns the population variance of the vector, or the sample variance (dividing
+/// by `n - 1` instead of `n`) if `sample` is set. It is `None` if it is empty, or if it only has a
+/// single value when computing the sample variance
+#[inline(always)]
+pub fn variancef64(a: &Vec<f64>, sample: &bool) -> Option<f64> {
+    let n = if *sample {
+        a.len().checked_sub(1)?
+    } else {
+        a.len()
+    };
+    if n == 0 {
+        return None;
+    }
+    let mean = meanf64(a)?;
+    Some(a.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / n as f64)
+}
+
+/// `stddevf64` returns the standard deviation of the vector, the square root of `variancef64`
+#[inline(always)]
+pub fn stddevf64(a: &Vec<f64>, sample: &bool) -> Option<f64> {
+    variancef64(a, sample).map(f64::sqrt)
+}
+
+/// `interleavearray` returns a new array alternating the values of the two arrays, starting with
+/// the first, followed by the rest of the longer array once the shorter one runs out
+#[inline(always)]
+pub fn interleavearray<T: std::clone::Clone>(a: &Vec<T>, b: &Vec<T>) -> Vec<T> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let shared = a.len().min(b.len());
+    for (x, y) in a.iter().zip(b.iter()) {
+        out.push(x.clone());
+        out.push(y.clone());
+    }
+    out.extend_from_slice(&a[shared..]);
+    out.extend_from_slice(&b[shared..]);
+    out
+}
+
+/// `equalsarray` returns true if both arrays have the same length and the same values in the same
+/// order
+#[inline(always)]
+pub fn equalsarray<T: PartialEq>(a: &Vec<T>, b: &Vec<T>) -> bool {
+    a == b
+}
+
+/// `approxequalsarray_f64` returns true if both arrays have the same length and each pair of values
+/// is no further apart than the provided epsilon, as exact equality is rarely useful for floats
+#[inline(always)]
+pub fn approxequalsarray_f64(a: &Vec<f64>, b: &Vec<f64>, epsilon: &f64) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b.iter())
+            .all(|(x, y)| (x - y).abs() <= *epsilon)
+}
+
+/// `zipvec_f64` combines each pair of values from the two arrays with the provided function,
+/// failing if the arrays are not the same length
+fn zipvec_f64(
+    a: &[f64],
+    b: &[f64],
+    op: &str,
+    f: impl Fn(f64, f64) -> f64,
+) -> Result<Vec<f64>, AlanError> {
+    if a.len() != b.len() {
+        return Err(format!(
+            "Elementwise {} requires equal-length arrays, got lengths {} and {}",
+            op,
+            a.len(),
+            b.len()
+        )
+        .into());
+    }
+    Ok(a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| f(*x, *y))
+        .collect::<Vec<f64>>())
+}
+
+/// `addvec_f64` adds the arrays together elementwise
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn addvec_f64(a: &Vec<f64>, b: &Vec<f64>) -> Result<Vec<f64>, AlanError> {
+    zipvec_f64(a, b, "addition", |x, y| x + y)
+}
+
+/// `subvec_f64` subtracts the second array from the first elementwise
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn subvec_f64(a: &Vec<f64>, b: &Vec<f64>) -> Result<Vec<f64>, AlanError> {
+    zipvec_f64(a, b, "subtraction", |x, y| x - y)
+}
+
+/// `mulvec_f64` multiplies the arrays together elementwise
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn mulvec_f64(a: &Vec<f64>, b: &Vec<f64>) -> Result<Vec<f64>, AlanError> {
+    zipvec_f64(a, b, "multiplication", |x, y| x * y)
+}
+
+/// `divvec_f64` divides the first array by the second elementwise. Unlike dividing single floats,
+/// dividing by zero is an error rather than an infinity or NaN hiding somewhere in the output
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn divvec_f64(a: &Vec<f64>, b: &Vec<f64>) -> Result<Vec<f64>, AlanError> {
+    if let Some(i) = b.iter().position(|y| *y == 0.0) {
+        return Err(format!("Elementwise division by zero at index {}", i).into());
+    }
+    zipvec_f64(a, b, "division", |x, y| x / y)
+}
+
+/// `scalevec_f64` multiplies every value in the array by the scalar
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn scalevec_f64(a: &Vec<f64>, s: &f64) -> Vec<f64> {
+    a.iter().map(|v| v * s).collect::<Vec<f64>>()
+}
+
+/// Buffer-related functions
+
+/// `getbuffer` returns the value at the given index presuming it exists
+#[inline(always)]
+pub fn getbuffer<T: std::clone::Clone, const S: usize>(b: &[T; S], i: &i64) -> Option<T> {
+    b.get(*i as usize).cloned()
+}
+
+/// `mapbuffer_onearg` runs the provided single-argument function on each element of the buffer,
+/// returning a new buffer
+#[inline(always)]
+pub fn mapbuffer_onearg<A, const N: usize, B>(v: &[A; N], mut m: impl FnMut(&A) -> B) -> [B; N] {
+    std::array::from_fn(|i| m(&v[i]))
+}
+
+/// `mapbuffertoarray` runs the provided function on each element of the buffer, returning a new
+/// array instead of a fixed-size buffer
+#[inline(always)]
+pub fn mapbuffertoarray<A, const N: usize, B>(v: &[A; N], f: impl FnMut(&A) -> B) -> Vec<B> {
+    v.iter().map(f).collect()
+}
+
+/// `mapbuffer_twoarg` runs the provided two-argument (value, index) function on each element of the
+/// buffer, returning a new buffer
+#[inline(always)]
+pub fn mapbuffer_twoarg<A, const N: usize, B: std::marker::Copy>(
+    v: &[A; N],
+    mut m: impl FnMut(&A, &i64) -> B,
+) -> [B; N] {
+    let mut out = [m(&v[0], &0); N];
+    for i in 1..N {
+        out[i] = m(&v[i], &(i as i64));
+    }
+    out
+}
+
+/// `reducebuffer_sametype` runs the provided function to reduce the buffer into a singular
+/// value
+#[inline(always)]
+pub fn reducebuffer_sametype<A: std::clone::Clone, const S: usize>(
+    b: &[A; S],
+    mut f: impl FnMut(&A, &A) -> A,
+) -> Option<A> {
+    // The built-in iter `reduce` is awkward for our use case
+    if b.len() == 0 {
+        None
+    } else if b.len() == 1 {
+        Some(b[0].clone())
+    } else {
+        let mut out = b[0].clone();
+        for i in 1..b.len() {
+            out = f(&out, &b[i]);
+        }
+        Some(out)
+    }
+}
+
+/// `reducebuffer_difftype` runs the provided function and initial value to reduce the buffer into a
+/// singular value. Because an initial value is provided, it always returns at least that value
+#[inline(always)]
+pub fn reducebuffer_difftype<A: std::clone::Clone, const S: usize, B: std::clone::Clone>(
+    b: &[A; S],
+    i: &B,
+    mut f: impl FnMut(&B, &A) -> B,
+) -> B {
+    let mut out = i.clone();
+    for i in 0..b.len() {
+        out = f(&out, &b[i]);
+    }
+    out
+}
+
+/// `reducebuffer_sametype_idx` runs the provided function to reduce the buffer into a singular
+/// value, also providing the index of the current element
+#[inline(always)]
+pub fn reducebuffer_sametype_idx<A: std::clone::Clone, const S: usize>(
+    b: &[A; S],
+    mut f: impl FnMut(&A, &A, &i64) -> A,
+) -> Option<A> {
+    // The built-in iter `reduce` is awkward for our use case
+    if b.len() == 0 {
+        None
+    } else if b.len() == 1 {
+        Some(b[0].clone())
+    } else {
+        let mut out = b[0].clone();
+        for i in 1..b.len() {
+            out = f(&out, &b[i], &(i as i64));
+        }
+        Some(out)
+    }
+}
+
+/// `reducebuffer_difftype_idx` runs the provided function and initial value to reduce the buffer
+/// into a singular value, also providing the index of the current element. Because an initial
+/// value is provided, it always returns at least that value
+#[inline(always)]
+pub fn reducebuffer_difftype_idx<A: std::clone::Clone, const S: usize, B: std::clone::Clone>(
+    b: &[A; S],
+    i: &B,
+    mut f: impl FnMut(&B, &A, &i64) -> B,
+) -> B {
+    let mut out = i.clone();
+    for i in 0..b.len() {
+        out = f(&out, &b[i], &(i as i64));
+    }
+    out
+}
+
+/// `hasbuffer` returns true if the specified value exists anywhere in the array
+#[inline(always)]
+pub fn hasbuffer<T: std::cmp::PartialEq, const S: usize>(a: &[T; S], v: &T) -> bool {
+    for val in a {
+        if val == v {
+            return true;
+        }
+    }
+    return false;
+}
+
+/// `hasfnbuffer` returns true if the check function returns true for any element of the array
+#[inline(always)]
+pub fn hasfnbuffer<T, const S: usize>(a: &[T; S], mut f: impl FnMut(&T) -> bool) -> bool {
+    for v in a {
+        if f(v) {
+            return true;
+        }
+    }
+    return false;
+}
+
+/// `findbuffer` returns the first value from the buffer that matches the check function, if any
+#[inline(always)]
+pub fn findbuffer<T: std::clone::Clone, const S: usize>(
+    a: &[T; S],
+    mut f: impl FnMut(&T) -> bool,
+) -> Option<T> {
+    for v in a {
+        if f(v) {
+            return Some(v.clone());
+        }
+    }
+    return None;
+}
+
+/// `filterbuffer` returns a new array containing only the values from the buffer that match the
+/// check function
+#[inline(always)]
+pub fn filterbuffer<T: std::clone::Clone, const S: usize>(
+    a: &[T; S],
+    mut f: impl FnMut(&T) -> bool,
+) -> Vec<T> {
+    let mut out = Vec::new();
+    for v in a {
+        if f(v) {
+            out.push(v.clone());
+        }
+    }
+    out
+}
+
+/// `everybuffer` returns true if every value in the array matches the check function
+#[inline(always)]
+pub fn everybuffer<T, const S: usize>(a: &[T; S], mut f: impl FnMut(&T) -> bool) -> bool {
+    for v in a {
+        if !f(v) {
+            return false;
+        }
+    }
+    return true;
+}
+
+/// `concatbuffer` mutates the first buffer given with the values of the other two. It depends on
+/// the provided buffer to be the right size to fit the data from both of the other buffers.
+#[inline(always)]
+pub fn concatbuffer<T: std::clone::Clone, const S: usize, const N: usize, const O: usize>(
+    o: &mut [T; O],
+    a: &[T; S],
+    b: &[T; N],
+) {
+    for (i, v) in a.iter().chain(b).enumerate() {
+        o[i] = v.clone();
+    }
+}
+
+/// `repeatbuffertoarray` returns a new array with the original buffer repeated N times
+#[inline(always)]
+pub fn repeatbuffertoarray<T: std::clone::Clone, const S: usize>(a: &[T; S], c: &i64) -> Vec<T> {
+    let mut out = Vec::new();
+    for _ in 0..*c {
+        for v in a {
+            out.push(v.clone());
+        }
+    }
+    out
+}
+
+/// `chunkbuffer` splits the buffer into successive arrays of the specified size, with the last one
+/// being shorter if the buffer does not divide evenly. It fails if the size is not positive
+#[inline(always)]
+pub fn chunkbuffer<T: std::clone::Clone, const S: usize>(
+    b: &[T; S],
+    size: &i64,
+) -> Result<Vec<Vec<T>>, AlanError> {
+    if *size <= 0 {
+        return Err(format!("Chunk size must be positive, got {}", size).into());
+    }
+    Ok(b.chunks(*size as usize).map(|c| c.to_vec()).collect())
+}
+
+/// `equalsbuffer` returns true if both buffers have the same values in the same order
+#[inline(always)]
+pub fn equalsbuffer<T: PartialEq, const S: usize>(a: &[T; S], b: &[T; S]) -> bool {
+    a == b
+}
+
+/// `storebuffer` stores the provided value in the specified index. If the index is out-of-bounds
+/// for the buffer it fails, otherwise it returns the old value.
+#[inline(always)]
+pub fn storebuffer<T: std::clone::Clone, const S: usize>(
+    a: &mut [T; S],
+    i: &i64,
+    v: &T,
+) -> Result<T, AlanError> {
+    match (*i as usize) < a.len() {
+        false => Err(format!(
+            "The provided index {} is out-of-bounds for the specified buffer",
+            i
+        )
+        .into()),
+        true => Ok(std::mem::replace(a.each_mut()[*i as usize], v.clone())),
+    }
+}
+
+/// `swapbuffer` swaps the values at the specified indicies (or fails if either index is out of
+/// bounds). It returns a Fallible void value.
+#[inline(always)]
+pub fn swapbuffer<T, const S: usize>(a: &mut [T; S], i: &i64, j: &i64) -> Result<(), AlanError> {
+    if *i < 0 {
+        return Err(format!(
+            "Provided buffer index {} is beyond the bounds of the buffer",
+            i
+        )
+        .into());
+    }
+    if *j < 0 {
+        return Err(format!(
+            "Provided buffer index {} is beyond the bounds of the buffer",
+            j
+        )
+        .into());
+    }
+    let i = *i as usize;
+    let j = *j as usize;
+    if i >= a.len() {
+        return Err(format!(
+            "Provided buffer index {} is beyond the bounds of the buffer",
+            i
+        )
+        .into());
+    }
+    if j >= a.len() {
+        return Err(format!(
+            "Provided buffer index {} is beyond the bounds of the buffer",
+            j
+        )
+        .into());
+    }
+    if i == j {
+        return Ok(());
+    }
+    if i < j {
+        let (i_section, j_section) = a.split_at_mut(j);
+        std::mem::swap(&mut i_section[i], &mut j_section[0]);
+    } else {
+        let (j_section, i_section) = a.split_at_mut(i);
+        std::mem::swap(&mut j_section[j], &mut i_section[0]);
+    }
+    Ok(())
+}
+
+/// `sortbuffer` is a thin wrapper around `sort_by` allowing for the sort decision to be done by
+/// numeric operation rather than the `Ordering` enum, which is not exposed
 #[inline(always)]
 pub fn sortbuffer<T, const S: usize>(a: &mut [T; S], mut sorter: impl FnMut(&T, &T) -> i8) {
     a.sort_by(|a, b| sorter(a, b).cmp(&0));
@@ -694,6 +1464,31 @@ pub fn getdict<K: std::hash::Hash + Eq, V: std::clone::Clone>(
     d.get(k).cloned()
 }
 
+/// `getordefaultdict` returns the value for the given key, or the default if it does not exist
+#[inline(always)]
+pub fn getordefaultdict<K: std::hash::Hash + Eq, V: std::clone::Clone>(
+    d: &OrderedHashMap<K, V>,
+    k: &K,
+    default: &V,
+) -> V {
+    d.get(k).unwrap_or(default).clone()
+}
+
+/// `getorinsertdict` returns the value for the given key, first inserting the default for it if it
+/// does not exist
+#[inline(always)]
+pub fn getorinsertdict<K: std::hash::Hash + Eq + std::clone::Clone, V: std::clone::Clone>(
+    d: &mut OrderedHashMap<K, V>,
+    k: &K,
+    default: &V,
+) -> V {
+    if let Some(v) = d.get(k) {
+        return v.clone();
+    }
+    d.insert(k.clone(), default.clone());
+    default.clone()
+}
+
 /// `keysdict` returns an array of keys from the dictionary
 #[inline(always)]
 pub fn keysdict<K: std::clone::Clone, V>(d: &OrderedHashMap<K, V>) -> Vec<K> {
@@ -741,6 +1536,151 @@ pub fn concatdict<K: std::clone::Clone + std::hash::Hash + Eq, V: std::clone::Cl
     out
 }
 
+/// `to_json` serializes a string-to-string dictionary into a JSON object, preserving key order
+#[inline(always)]
+pub fn to_json(d: &OrderedHashMap<String, String>) -> String {
+    let mut out = serde_json::Map::new();
+    for (k, v) in d.iter() {
+        out.insert(k.clone(), serde_json::Value::String(v.clone()));
+    }
+    serde_json::Value::Object(out).to_string()
+}
+
+/// `parse_json` parses a JSON object whose values are all strings into a dictionary, preserving
+/// key order. Malformed JSON, or any other kind of JSON value, is an error
+#[inline(always)]
+pub fn parse_json(s: &String) -> Result<OrderedHashMap<String, String>, AlanError> {
+    let obj = match serde_json::from_str::<serde_json::Value>(s) {
+        Ok(serde_json::Value::Object(obj)) => obj,
+        Ok(_) => return Err("Expected a JSON object".into()),
+        Err(e) => return Err(format!("Invalid JSON: {}", e).into()),
+    };
+    let mut out = OrderedHashMap::new();
+    for (k, v) in obj {
+        match v {
+            serde_json::Value::String(v) => {
+                out.insert(k, v);
+            }
+            _ => return Err(format!("Expected a string value for key {}", k).into()),
+        }
+    }
+    Ok(out)
+}
+
+/// LRU cache-related bindings
+
+/// `LruCache` is a dictionary with a maximum number of entries. The `OrderedHashMap` is kept in
+/// order of use, so the least-recently-used entry is always the first one and is the one evicted
+/// when a new key is stored in a full cache
+#[derive(Debug)]
+pub struct LruCache<K, V> {
+    capacity: usize,
+    map: OrderedHashMap<K, V>,
+}
+
+// `OrderedHashMap` is only `Clone` when its keys can be hashed, which `derive` can't express
+impl<K: std::clone::Clone + std::hash::Hash + Eq, V: std::clone::Clone> Clone for LruCache<K, V> {
+    fn clone(&self) -> Self {
+        LruCache {
+            capacity: self.capacity,
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<K: std::clone::Clone + std::hash::Hash + Eq, V: std::clone::Clone> LruCache<K, V> {
+    pub fn new(capacity: i64) -> LruCache<K, V> {
+        LruCache {
+            capacity: capacity.max(0) as usize,
+            map: OrderedHashMap::new(),
+        }
+    }
+
+    /// Returns the value for the given key, if it exists, and marks it as the most recently used
+    pub fn get(&mut self, k: &K) -> Option<V> {
+        let v = self.map.remove(k)?;
+        self.map.insert(k.clone(), v.clone());
+        Some(v)
+    }
+
+    /// Stores the value as the most recently used entry, returning the value of the entry evicted
+    /// to make room for it, if any
+    pub fn put(&mut self, k: K, v: V) -> Option<V> {
+        if self.capacity == 0 {
+            return Some(v);
+        }
+        if self.map.remove(&k).is_some() {
+            self.map.insert(k, v);
+            return None;
+        }
+        let evicted = if self.map.len() >= self.capacity {
+            let oldest = self.map.keys().next().cloned()?;
+            self.map.remove(&oldest)
+        } else {
+            None
+        };
+        self.map.insert(k, v);
+        evicted
+    }
+
+    pub fn len(&self) -> i64 {
+        self.map.len() as i64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// Memoization-related bindings
+
+/// `Memoized` wraps a single-argument function and caches its result for each distinct argument,
+/// so the function is only invoked once per key. This is only correct for pure functions, as the
+/// side effects of the function will not happen again for a key that has already been cached.
+#[derive(Debug)]
+pub struct Memoized<K, V> {
+    f: fn(&K) -> V,
+    cache: OrderedHashMap<K, V>,
+    invocations: i64,
+}
+
+// Like `LruCache`, the `OrderedHashMap` inside prevents deriving `Clone`
+impl<K: std::clone::Clone + std::hash::Hash + Eq, V: std::clone::Clone> Clone for Memoized<K, V> {
+    fn clone(&self) -> Self {
+        Memoized {
+            f: self.f,
+            cache: self.cache.clone(),
+            invocations: self.invocations,
+        }
+    }
+}
+
+impl<K: std::clone::Clone + std::hash::Hash + Eq, V: std::clone::Clone> Memoized<K, V> {
+    pub fn new(f: fn(&K) -> V) -> Memoized<K, V> {
+        Memoized {
+            f,
+            cache: OrderedHashMap::new(),
+            invocations: 0,
+        }
+    }
+
+    /// Returns the cached result for the given key, invoking the wrapped function on a cache miss
+    pub fn call(&mut self, k: &K) -> V {
+        if let Some(v) = self.cache.get(k) {
+            return v.clone();
+        }
+        let v = (self.f)(k);
+        self.invocations += 1;
+        self.cache.insert(k.clone(), v.clone());
+        v
+    }
+
+    /// Returns the number of times the wrapped function has actually been invoked
+    pub fn calls(&self) -> i64 {
+        self.invocations
+    }
+}
+
 /// Set-related bindings
 
 /// `arrayset` returns an array of values in the set
@@ -792,38 +1732,362 @@ pub fn symmetric_differenceset<V: std::clone::Clone + std::hash::Hash + Eq>(
         .collect::<HashSet<V>>()
 }
 
-/// `productset` returns the product of the original two sets (a set of tuples of all combinations
-/// of values in each set)
+/// `productset` returns the product of the original two sets (a set of tuples of all combinations
+/// of values in each set)
+#[inline(always)]
+pub fn productset<V: std::clone::Clone + std::hash::Hash + Eq>(
+    a: &HashSet<V>,
+    b: &HashSet<V>,
+) -> HashSet<(V, V)> {
+    let mut out = HashSet::new();
+    for va in a.iter() {
+        for vb in b.iter() {
+            out.insert((va.clone(), vb.clone()));
+        }
+    }
+    out
+}
+
+/// Float-related functions
+
+/// `maprangef64` linearly remaps the value from the input range to the output range. Values
+/// outside of the input range are extrapolated. An empty input range is an error
+#[inline(always)]
+pub fn maprangef64(
+    x: &f64,
+    in_lo: &f64,
+    in_hi: &f64,
+    out_lo: &f64,
+    out_hi: &f64,
+) -> Result<f64, AlanError> {
+    if in_lo == in_hi {
+        return Err("Cannot map from an empty range".into());
+    }
+    Ok(out_lo + (x - in_lo) * (out_hi - out_lo) / (in_hi - in_lo))
+}
+
+/// `maprange_clamped` is `maprangef64` with the result kept within the output range
+#[inline(always)]
+pub fn maprange_clamped(
+    x: &f64,
+    in_lo: &f64,
+    in_hi: &f64,
+    out_lo: &f64,
+    out_hi: &f64,
+) -> Result<f64, AlanError> {
+    let v = maprangef64(x, in_lo, in_hi, out_lo, out_hi)?;
+    Ok(v.clamp(out_lo.min(*out_hi), out_lo.max(*out_hi)))
+}
+
+/// Vector-related functions
+
+pub fn cross_f32(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn cross_f64(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// `anglebetween_f32` returns the angle between two vectors in radians. There is no meaningful
+/// angle for a zero-length vector, so that is an error
+pub fn anglebetween_f32(a: &[f32; 3], b: &[f32; 3]) -> Result<f32, AlanError> {
+    let dot = a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    let mag_a = (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt();
+    let mag_b = (b[0] * b[0] + b[1] * b[1] + b[2] * b[2]).sqrt();
+    if mag_a == 0.0 || mag_b == 0.0 {
+        return Err("Cannot compute the angle of a zero-length vector".into());
+    }
+    // Rounding can push the cosine just outside of [-1, 1], which `acos` turns into NaN
+    Ok((dot / (mag_a * mag_b)).clamp(-1.0, 1.0).acos())
+}
+
+#[inline(always)]
+pub fn radians_to_degrees(r: &f32) -> f32 {
+    r.to_degrees()
+}
+
+#[inline(always)]
+pub fn degrees_to_radians(d: &f32) -> f32 {
+    d.to_radians()
+}
+
+/// File-related functions
+
+/// `readfile` reads the entire contents of the file at the provided path into a string
+#[inline(always)]
+pub fn readfile(path: &String) -> Result<String, AlanError> {
+    Ok(std::fs::read_to_string(path)?)
+}
+
+/// `readfilebytes` reads the entire contents of the file at the provided path as raw bytes
+#[inline(always)]
+pub fn readfilebytes(path: &String) -> Result<Vec<u8>, AlanError> {
+    Ok(std::fs::read(path)?)
+}
+
+/// `writefile` writes the provided string to the file at the provided path, creating the file if
+/// it does not exist and replacing its contents if it does
+#[inline(always)]
+pub fn writefile(path: &String, contents: &String) -> Result<(), AlanError> {
+    Ok(std::fs::write(path, contents)?)
+}
+
+/// `appendfile` appends the provided string to the end of the file at the provided path, creating
+/// the file if it does not exist
+#[inline(always)]
+pub fn appendfile(path: &String, contents: &String) -> Result<(), AlanError> {
+    use std::io::Write;
+    let mut f = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    Ok(f.write_all(contents.as_bytes())?)
+}
+
+/// Environment-related functions
+
+/// `getenv` returns the value of the environment variable, if it is set and valid unicode
+#[inline(always)]
+pub fn getenv(key: &String) -> Option<String> {
+    std::env::var(key).ok()
+}
+
+/// `setenv` sets the environment variable for this process and any child processes it spawns.
+/// Other threads reading the environment at the same time may see inconsistent values on some
+/// platforms, so this is best done early, before spawning threads
+#[inline(always)]
+pub fn setenv(key: &String, val: &String) {
+    std::env::set_var(key, val);
+}
+
+/// `envvars` returns all of the environment variables for this process that are valid unicode
+#[inline(always)]
+pub fn envvars() -> OrderedHashMap<String, String> {
+    let mut out = OrderedHashMap::new();
+    for (k, v) in std::env::vars() {
+        out.insert(k, v);
+    }
+    out
+}
+
+/// Subprocess-related types and functions
+
+/// `ExecResult` is the captured output and exit code of a subprocess that has finished running
+#[derive(Clone, Debug)]
+pub struct ExecResult {
+    pub stdout: String,
+    pub stderr: String,
+    pub code: i64,
+}
+
+/// `exec` runs the command with the provided arguments and waits for it to finish, capturing its
+/// output rather than inheriting this process's stdio. A non-zero exit code is still a successful
+/// `ExecResult`; only failing to spawn the command is an error. If the subprocess was terminated
+/// by a signal it has no exit code, and `-1` is used instead
+#[inline(always)]
+pub fn exec(cmd: &String, args: &Vec<String>) -> Result<ExecResult, AlanError> {
+    let out = std::process::Command::new(cmd)
+        .args(args)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .map_err(|e| AlanError::from(format!("Could not run {}: {}", cmd, e)))?;
+    Ok(ExecResult {
+        stdout: String::from_utf8_lossy(&out.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&out.stderr).to_string(),
+        code: out.status.code().map(|c| c as i64).unwrap_or(-1),
+    })
+}
+
+/// Random number-related types and functions
+
+/// `RngState` holds the state of a small SplitMix64 pseudo-random number generator. It is *not*
+/// cryptographically secure, but the same seed always produces the same sequence of values, and
+/// keeping the state explicit means separate threads can each own their own generator.
+#[derive(Clone, Debug)]
+pub struct RngState {
+    state: u64,
+}
+
+/// `seed_rng` creates a new random number generator state from the provided seed
+#[inline(always)]
+pub fn seed_rng(seed: &i64) -> RngState {
+    RngState {
+        state: *seed as u64,
+    }
+}
+
+/// `next_i64` advances the random number generator and returns the next 64-bit integer
+#[inline(always)]
+pub fn next_i64(state: &mut RngState) -> i64 {
+    state.state = state.state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (z ^ (z >> 31)) as i64
+}
+
+/// `next_f64` advances the random number generator and returns a float in the range `[0, 1)`
+#[inline(always)]
+pub fn next_f64(state: &mut RngState) -> f64 {
+    // Use the top 53 bits so every possible output is exactly representable
+    ((next_i64(state) as u64) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Time-related functions
+
+/// `now_millis` returns the number of milliseconds since the Unix epoch (1970-01-01T00:00:00Z)
+/// according to the system clock. The system clock can be adjusted while the program runs, so
+/// this is *not* guaranteed to be monotonic. Use `monotonic_nanos` to measure elapsed time.
+#[inline(always)]
+pub fn now_millis() -> i64 {
+    match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
+/// `now_nanos` returns the number of nanoseconds since the Unix epoch according to the system
+/// clock, with the same caveats as `now_millis`
+#[inline(always)]
+pub fn now_nanos() -> i64 {
+    match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        Ok(d) => d.as_nanos() as i64,
+        Err(e) => -(e.duration().as_nanos() as i64),
+    }
+}
+
+static MONOTONIC_START: OnceLock<std::time::Instant> = OnceLock::new();
+
+/// `monotonic_nanos` returns the number of nanoseconds since the first time it was called in this
+/// process. It never goes backwards, but its value is only meaningful relative to other calls
+#[inline(always)]
+pub fn monotonic_nanos() -> i64 {
+    MONOTONIC_START
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_nanos() as i64
+}
+
+/// Byte serialization functions
+
+/// `frombytes` splits the bytes into `N`-byte chunks and converts each of them with the provided
+/// function, failing if the number of bytes is not a multiple of `N`
+fn frombytes<T, const N: usize>(b: &[u8], f: fn([u8; N]) -> T) -> Result<Vec<T>, AlanError> {
+    if b.len() % N != 0 {
+        return Err(format!(
+            "The byte array length {} is not a multiple of {}",
+            b.len(),
+            N
+        )
+        .into());
+    }
+    Ok(b.chunks_exact(N)
+        .map(|c| f(c.try_into().unwrap()))
+        .collect::<Vec<T>>())
+}
+
+/// `asbytes_f32` returns the values as little-endian bytes, the same layout `create_buffer_init`
+/// uploads to the GPU
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn asbytes_f32(a: &Vec<f32>) -> Vec<u8> {
+    a.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>()
+}
+
+/// `frombytes_f32` reads little-endian `f32`s back out of the bytes, failing if the number of
+/// bytes is not a multiple of four
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn frombytes_f32(b: &Vec<u8>) -> Result<Vec<f32>, AlanError> {
+    frombytes(b, f32::from_le_bytes)
+}
+
+/// `asbytes_f64` returns the values as little-endian bytes
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn asbytes_f64(a: &Vec<f64>) -> Vec<u8> {
+    a.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>()
+}
+
+/// `frombytes_f64` reads little-endian `f64`s back out of the bytes, failing if the number of
+/// bytes is not a multiple of eight
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn frombytes_f64(b: &Vec<u8>) -> Result<Vec<f64>, AlanError> {
+    frombytes(b, f64::from_le_bytes)
+}
+
+/// `asbytes_i64` returns the values as little-endian bytes
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn asbytes_i64(a: &Vec<i64>) -> Vec<u8> {
+    a.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>()
+}
+
+/// `frombytes_i64` reads little-endian `i64`s back out of the bytes, failing if the number of
+/// bytes is not a multiple of eight
+#[allow(clippy::ptr_arg)]
+#[inline(always)]
+pub fn frombytes_i64(b: &Vec<u8>) -> Result<Vec<i64>, AlanError> {
+    frombytes(b, i64::from_le_bytes)
+}
+
+/// Hashing-related functions
+
+/// `sha256hex` returns the SHA-256 digest of the provided bytes as a lowercase hex string
 #[inline(always)]
-pub fn productset<V: std::clone::Clone + std::hash::Hash + Eq>(
-    a: &HashSet<V>,
-    b: &HashSet<V>,
-) -> HashSet<(V, V)> {
-    let mut out = HashSet::new();
-    for va in a.iter() {
-        for vb in b.iter() {
-            out.insert((va.clone(), vb.clone()));
+pub fn sha256hex(data: &Vec<u8>) -> String {
+    use sha2::Digest;
+    format!("{:x}", sha2::Sha256::digest(data))
+}
+
+/// `crc32` returns the CRC-32 (IEEE) checksum of the provided bytes
+#[inline(always)]
+pub fn crc32(data: &Vec<u8>) -> i64 {
+    crc32fast::hash(data) as i64
+}
+
+/// `Fnv1a` is a 64-bit FNV-1a hasher. Unlike the hasher `RandomState` provides it always starts
+/// from the same state, so the hashes it produces are the same from run to run
+struct Fnv1a(u64);
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
         }
     }
-    out
 }
 
-/// Vector-related functions
-
-pub fn cross_f32(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
-    [
-        a[1] * b[2] - a[2] * b[1],
-        a[2] * b[0] - a[0] * b[2],
-        a[0] * b[1] - a[1] * b[0],
-    ]
+/// `hasharray` returns a stable hash of the contents of the array, suitable for keying a cache
+#[inline(always)]
+pub fn hasharray<T: Hash>(a: &Vec<T>) -> i64 {
+    let mut h = Fnv1a(0xcbf29ce484222325);
+    a.hash(&mut h);
+    h.finish() as i64
 }
 
-pub fn cross_f64(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
-    [
-        a[1] * b[2] - a[2] * b[1],
-        a[2] * b[0] - a[0] * b[2],
-        a[0] * b[1] - a[1] * b[0],
-    ]
+/// `hashbuffer` returns a stable hash of the contents of the buffer. It matches `hasharray` for
+/// the same values
+#[inline(always)]
+pub fn hashbuffer<T: Hash, const S: usize>(a: &[T; S]) -> i64 {
+    let mut h = Fnv1a(0xcbf29ce484222325);
+    a.hash(&mut h);
+    h.finish() as i64
 }
 
 /// GPU-related functions and types
@@ -845,40 +2109,134 @@ impl GPU {
         }
         out
     }
-    pub fn init(adapters: Vec<wgpu::Adapter>) -> Vec<GPU> {
+    /// Initializes every adapter with all of the features and limits it supports. Adapters that
+    /// fail to initialize are skipped, and the reasons why are returned alongside the GPUs
+    pub fn init(adapters: Vec<wgpu::Adapter>) -> (Vec<GPU>, Vec<String>) {
         let mut out = Vec::new();
+        let mut skipped = Vec::new();
+        if adapters.is_empty() {
+            skipped.push("No WebGPU-compliant GPUs were found".to_string());
+        }
         for adapter in adapters {
             let features = adapter.features();
             let limits = adapter.limits();
+            match GPU::request(adapter, features, limits) {
+                Ok(g) => out.push(g),
+                Err(e) => skipped.push(e),
+            }
+        }
+        (out, skipped)
+    }
+    /// Initializes the adapters with only the specified features and limits, skipping the
+    /// adapters that can't provide them. Without specified limits each adapter's own limits are
+    /// used. `SHADER_F16` is also requested from adapters that support it, since the `f16` buffers
+    /// depend on it but it isn't required to use the GPU
+    pub fn init_with(
+        adapters: Vec<wgpu::Adapter>,
+        features: wgpu::Features,
+        limits: Option<wgpu::Limits>,
+    ) -> (Vec<GPU>, Vec<String>) {
+        let mut out = Vec::new();
+        let mut skipped = Vec::new();
+        if adapters.is_empty() {
+            skipped.push("No WebGPU-compliant GPUs were found".to_string());
+        }
+        for adapter in adapters {
             let info = adapter.get_info();
-            let device_future = adapter.request_device(
-                &wgpu::DeviceDescriptor {
-                    label: Some(&format!("{} on {}", info.name, info.backend.to_str())),
-                    required_features: features,
-                    required_limits: limits,
-                    memory_hints: wgpu::MemoryHints::Performance,
-                },
-                None,
-            );
-            match futures::executor::block_on(device_future) {
-                Ok((device, queue)) => {
-                    out.push(GPU {
-                        adapter,
-                        device,
-                        queue,
-                    });
+            let name = format!("{} on {}", info.name, info.backend.to_str());
+            let missing = features.difference(adapter.features());
+            if !missing.is_empty() {
+                skipped.push(format!(
+                    "{} does not support the required features {:?}",
+                    name, missing
+                ));
+                continue;
+            }
+            let limits = match &limits {
+                Some(l) if !l.check_limits(&adapter.limits()) => {
+                    skipped.push(format!("{} does not meet the required limits", name));
+                    continue;
                 }
-                Err(_) => { /* Do nothing */ }
+                Some(l) => l.clone(),
+                None => adapter.limits(),
             };
+            let optional = adapter.features() & wgpu::Features::SHADER_F16;
+            match GPU::request(adapter, features | optional, limits) {
+                Ok(g) => out.push(g),
+                Err(e) => skipped.push(e),
+            }
+        }
+        (out, skipped)
+    }
+    fn request(
+        adapter: wgpu::Adapter,
+        features: wgpu::Features,
+        limits: wgpu::Limits,
+    ) -> Result<GPU, String> {
+        let info = adapter.get_info();
+        let name = format!("{} on {}", info.name, info.backend.to_str());
+        let device_future = adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some(&name),
+                required_features: features,
+                required_limits: limits,
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        );
+        match futures::executor::block_on(device_future) {
+            Ok((device, queue)) => Ok(GPU {
+                adapter,
+                device,
+                queue,
+            }),
+            Err(e) => Err(format!("{} could not be initialized: {}", name, e)),
         }
-        out
     }
 }
 
-static GPUS: OnceLock<Vec<GPU>> = OnceLock::new();
+static GPUS: OnceLock<(Vec<GPU>, Vec<String>)> = OnceLock::new();
+
+/// `gpus` initializes the GPUs the first time it is called. No particular features are required,
+/// as requesting every feature an adapter offers fails on some drivers, but each adapter's own
+/// limits are, so buffers and workgroups aren't capped at the smaller WebGPU defaults
+fn gpus() -> &'static (Vec<GPU>, Vec<String>) {
+    GPUS.get_or_init(|| GPU::init_with(GPU::list(), wgpu::Features::empty(), None))
+}
+
+/// `gpu_available` returns whether there is a usable GPU, without panicking if there isn't, so
+/// programs can fall back to a CPU implementation
+#[inline(always)]
+pub fn gpu_available() -> bool {
+    !gpus().0.is_empty()
+}
+
+/// `gpu_init_report` returns the reasons any GPUs were skipped while initializing them
+#[inline(always)]
+pub fn gpu_init_report() -> Vec<String> {
+    gpus().1.clone()
+}
+
+/// `gpu_init_report_with` initializes the GPUs again, requiring the named features, and returns the
+/// reasons any were skipped. Features can be named like `SHADER_F16` or `shader-f16`. The GPUs the
+/// rest of the program uses are unaffected
+pub fn gpu_init_report_with(features: &Vec<String>) -> Vec<String> {
+    let mut required = wgpu::Features::empty();
+    let mut unknown = Vec::new();
+    for name in features {
+        match wgpu::Features::from_name(&name.to_uppercase().replace("-", "_")) {
+            Some(f) => required |= f,
+            None => unknown.push(name.clone()),
+        }
+    }
+    if !unknown.is_empty() {
+        return vec![format!("Unknown GPU features {}", unknown.join(", "))];
+    }
+    GPU::init_with(GPU::list(), required, None).1
+}
 
 fn gpu() -> &'static GPU {
-    match GPUS.get_or_init(|| GPU::init(GPU::list())).get(0) {
+    match gpus().0.get(0) {
         Some(g) => g,
         None => panic!(
             "This program requires a GPU but there are no WebGPU-compliant GPUs on this machine"
@@ -891,6 +2249,9 @@ pub struct GBuffer {
     buffer: Rc<wgpu::Buffer>,
     id: String,
     element_size: i8,
+    // Buffers are padded to a multiple of four bytes, so for elements smaller than that the
+    // buffer can have room for more elements than it actually holds
+    len: i64,
 }
 
 impl PartialEq for GBuffer {
@@ -941,7 +2302,34 @@ pub fn create_buffer_init<T>(
         )),
         id: format!("buffer_{}", format!("{}", Uuid::new_v4()).replace("-", "_")),
         element_size: *element_size,
+        len: vals.len() as i64,
+    }
+}
+
+/// `create_buffer_init_f16` converts the values to half-precision floats and uploads those, for
+/// shaders using `f16`. This requires the GPU to support the `SHADER_F16` feature. Buffers must be
+/// a multiple of four bytes, so an odd number of values gets a trailing zero that isn't counted in
+/// the length of the buffer
+#[allow(clippy::ptr_arg)]
+pub fn create_buffer_init_f16(
+    usage: &wgpu::BufferUsages,
+    vals: &Vec<f32>,
+) -> Result<GBuffer, AlanError> {
+    if !gpu().device.features().contains(wgpu::Features::SHADER_F16) {
+        return Err("This GPU does not support f16 shaders".into());
     }
+    let halves = vals
+        .iter()
+        .map(|v| half::f16::from_f32(*v))
+        .collect::<Vec<half::f16>>();
+    Ok(create_buffer_init(usage, &halves, &2))
+}
+
+/// `create_buffer_init_bytes` uploads raw bytes, like binary data read from a file, with an
+/// element size of one byte. Buffers must be a multiple of four bytes, so the tail is zero-padded
+#[allow(clippy::ptr_arg)]
+pub fn create_buffer_init_bytes(usage: &wgpu::BufferUsages, bytes: &Vec<u8>) -> GBuffer {
+    create_buffer_init(usage, bytes, &1)
 }
 
 pub fn create_empty_buffer(usage: &wgpu::BufferUsages, size: &i64, element_size: &i8) -> GBuffer {
@@ -955,6 +2343,7 @@ pub fn create_empty_buffer(usage: &wgpu::BufferUsages, size: &i64, element_size:
         })),
         id: format!("buffer_{}", format!("{}", Uuid::new_v4()).replace("-", "_")),
         element_size: *element_size,
+        len: *size,
     }
 }
 
@@ -975,9 +2364,19 @@ pub fn storage_buffer_type() -> wgpu::BufferUsages {
     wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC
 }
 
+/// Buffers used for indirect dispatch are written by one compute shader and then read by the GPU
+/// to determine the workgroup count of another
+#[inline(always)]
+pub fn indirect_buffer_type() -> wgpu::BufferUsages {
+    wgpu::BufferUsages::STORAGE
+        | wgpu::BufferUsages::INDIRECT
+        | wgpu::BufferUsages::COPY_DST
+        | wgpu::BufferUsages::COPY_SRC
+}
+
 #[inline(always)]
 pub fn bufferlen(gb: &GBuffer) -> i64 {
-    (gb.size() as i64) / (gb.element_size as i64)
+    gb.len
 }
 
 #[inline(always)]
@@ -985,13 +2384,15 @@ pub fn buffer_id(b: &GBuffer) -> String {
     b.id.clone()
 }
 
+#[derive(Clone)]
 pub struct GPGPU {
     pub source: String,
     pub entrypoint: String,
     pub buffers: Vec<Vec<GBuffer>>,
     pub workgroup_sizes: [i64; 3],
-    pub module: Option<wgpu::ShaderModule>,
-    pub compute_pipeline: Option<wgpu::ComputePipeline>,
+    pub indirect: Option<GBuffer>,
+    pub module: Option<Rc<wgpu::ShaderModule>>,
+    pub compute_pipeline: Option<Rc<wgpu::ComputePipeline>>,
 }
 
 impl GPGPU {
@@ -1001,23 +2402,63 @@ impl GPGPU {
             entrypoint: "main".to_string(),
             buffers,
             workgroup_sizes,
+            indirect: None,
             module: None,
             compute_pipeline: None,
         }
     }
+    /// Creates a GPGPU whose workgroup counts are read from the first three `u32`s of the
+    /// `indirect` buffer at dispatch time, instead of being fixed on the CPU side
+    pub fn new_indirect(
+        source: String,
+        buffers: Vec<Vec<GBuffer>>,
+        indirect: GBuffer,
+    ) -> Result<GPGPU, AlanError> {
+        if indirect.size() < 12 {
+            return Err(
+                "The indirect dispatch buffer must hold at least three u32 values (12 bytes)"
+                    .into(),
+            );
+        }
+        Ok(GPGPU {
+            source,
+            entrypoint: "main".to_string(),
+            buffers,
+            workgroup_sizes: [1, 1, 1],
+            indirect: Some(indirect),
+            module: None,
+            compute_pipeline: None,
+        })
+    }
+    /// Replaces the buffers bound to the compute shader while keeping the cached module and
+    /// pipeline, so the same shader can be re-run on different data of the same shape
+    pub fn set_buffers(&mut self, buffers: Vec<Vec<GBuffer>>) {
+        debug_assert!(
+            self.buffers.len() == buffers.len()
+                && self
+                    .buffers
+                    .iter()
+                    .zip(buffers.iter())
+                    .all(|(old, new)| old.len() == new.len()),
+            "The new buffers do not match the bind group layout of the GPGPU"
+        );
+        self.buffers = buffers;
+    }
 }
 
 pub fn gpu_run(gg: &mut GPGPU) {
     let g = gpu();
     if gg.module.is_none() {
-        gg.module = Some(g.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(&gg.source)),
-        }));
+        gg.module = Some(Rc::new(g.device.create_shader_module(
+            wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(&gg.source)),
+            },
+        )));
     }
     let module = gg.module.as_ref().unwrap();
     if gg.compute_pipeline.is_none() {
-        gg.compute_pipeline = Some(g.device.create_compute_pipeline(
+        gg.compute_pipeline = Some(Rc::new(g.device.create_compute_pipeline(
             &wgpu::ComputePipelineDescriptor {
                 label: None,
                 layout: None,
@@ -1026,7 +2467,7 @@ pub fn gpu_run(gg: &mut GPGPU) {
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
                 cache: None,
             },
-        ));
+        )));
     }
     let compute_pipeline = gg.compute_pipeline.as_ref().unwrap();
     let mut bind_groups = Vec::new();
@@ -1060,11 +2501,14 @@ pub fn gpu_run(gg: &mut GPGPU) {
             // The Rust borrow checker is forcing my hand here
             cpass.set_bind_group(i.try_into().unwrap(), &bind_groups[i], &[]);
         }
-        cpass.dispatch_workgroups(
-            gg.workgroup_sizes[0].try_into().unwrap(),
-            gg.workgroup_sizes[1].try_into().unwrap(),
-            gg.workgroup_sizes[2].try_into().unwrap(),
-        );
+        match &gg.indirect {
+            Some(indirect) => cpass.dispatch_workgroups_indirect(indirect, 0),
+            None => cpass.dispatch_workgroups(
+                gg.workgroup_sizes[0].try_into().unwrap(),
+                gg.workgroup_sizes[1].try_into().unwrap(),
+                gg.workgroup_sizes[2].try_into().unwrap(),
+            ),
+        }
     }
     g.queue.submit(Some(encoder.finish()));
 }
@@ -1076,14 +2520,16 @@ pub fn gpu_run_list(ggs: &mut Vec<GPGPU>) {
         .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
     for gg in ggs {
         if gg.module.is_none() {
-            gg.module = Some(g.device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: None,
-                source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(&gg.source)),
-            }));
+            gg.module = Some(Rc::new(g.device.create_shader_module(
+                wgpu::ShaderModuleDescriptor {
+                    label: None,
+                    source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(&gg.source)),
+                },
+            )));
         }
         let module = gg.module.as_ref().unwrap();
         if gg.compute_pipeline.is_none() {
-            gg.compute_pipeline = Some(g.device.create_compute_pipeline(
+            gg.compute_pipeline = Some(Rc::new(g.device.create_compute_pipeline(
                 &wgpu::ComputePipelineDescriptor {
                     label: None,
                     layout: None,
@@ -1092,7 +2538,7 @@ pub fn gpu_run_list(ggs: &mut Vec<GPGPU>) {
                     compilation_options: wgpu::PipelineCompilationOptions::default(),
                     cache: None,
                 },
-            ));
+            )));
         }
         let compute_pipeline = gg.compute_pipeline.as_ref().unwrap();
         let mut bind_groups = Vec::new();
@@ -1124,19 +2570,32 @@ pub fn gpu_run_list(ggs: &mut Vec<GPGPU>) {
                 // The Rust borrow checker is forcing my hand here
                 cpass.set_bind_group(i.try_into().unwrap(), &bind_groups[i], &[]);
             }
-            cpass.dispatch_workgroups(
-                gg.workgroup_sizes[0].try_into().unwrap(),
-                gg.workgroup_sizes[1].try_into().unwrap(),
-                gg.workgroup_sizes[2].try_into().unwrap(),
-            );
+            match &gg.indirect {
+                Some(indirect) => cpass.dispatch_workgroups_indirect(indirect, 0),
+                None => cpass.dispatch_workgroups(
+                    gg.workgroup_sizes[0].try_into().unwrap(),
+                    gg.workgroup_sizes[1].try_into().unwrap(),
+                    gg.workgroup_sizes[2].try_into().unwrap(),
+                ),
+            }
         }
     }
     g.queue.submit(Some(encoder.finish()));
 }
 
+/// `gpu_wait_idle` blocks until all of the work submitted to the GPU has completed
+#[inline(always)]
+pub fn gpu_wait_idle() {
+    gpu().device.poll(wgpu::Maintain::wait()).panic_on_timeout();
+}
+
 pub fn read_buffer<T: std::clone::Clone>(b: &GBuffer) -> Vec<T> {
     let g = gpu();
-    let temp_buffer = create_empty_buffer(&map_read_buffer_type(), &bufferlen(b), &b.element_size);
+    let temp_buffer = create_empty_buffer(
+        &map_read_buffer_type(),
+        &((b.size() as i64) / (b.element_size as i64)),
+        &b.element_size,
+    );
     let mut encoder = g
         .device
         .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
@@ -1149,9 +2608,8 @@ pub fn read_buffer<T: std::clone::Clone>(b: &GBuffer) -> Vec<T> {
     if let Ok(Ok(())) = receiver.recv() {
         let data = temp_slice.get_mapped_range();
         let data_ptr = data.as_ptr();
-        let data_len = bufferlen(b) as usize;
         let data_slice: &[T] =
-            unsafe { std::slice::from_raw_parts(data_ptr as *const T, data_len) };
+            unsafe { std::slice::from_raw_parts(data_ptr as *const T, bufferlen(b) as usize) };
         let result = data_slice.to_vec();
         drop(data);
         temp_buffer.unmap();
@@ -1161,6 +2619,19 @@ pub fn read_buffer<T: std::clone::Clone>(b: &GBuffer) -> Vec<T> {
     }
 }
 
+/// `read_buffer_f16` reads a buffer of half-precision floats back as regular `f32`s
+pub fn read_buffer_f16(b: &GBuffer) -> Vec<f32> {
+    read_buffer::<half::f16>(b)
+        .into_iter()
+        .map(|v| v.to_f32())
+        .collect::<Vec<f32>>()
+}
+
+/// `read_buffer_bytes` reads a buffer back as raw bytes, without the padding at the end
+pub fn read_buffer_bytes(b: &GBuffer) -> Vec<u8> {
+    read_buffer::<u8>(b)
+}
+
 #[allow(clippy::ptr_arg)]
 pub fn replace_buffer<T>(b: &GBuffer, v: &Vec<T>) -> Result<(), AlanError> {
     if v.len() as i64 != bufferlen(b) {
@@ -1178,6 +2649,21 @@ pub fn replace_buffer<T>(b: &GBuffer, v: &Vec<T>) -> Result<(), AlanError> {
     }
 }
 
+#[inline(always)]
+pub fn copy_buffer(src: &GBuffer, dst: &GBuffer) -> Result<(), AlanError> {
+    if src.size() != dst.size() {
+        Err("The source and destination buffers are not the same size".into())
+    } else {
+        let g = gpu();
+        let mut encoder = g
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(src, 0, dst, 0, src.size());
+        g.queue.submit(Some(encoder.finish()));
+        Ok(())
+    }
+}
+
 /// Window-related types and functions
 pub struct AlanWindowContext {
     window: Option<std::sync::Arc<Window>>,
@@ -1185,22 +2671,50 @@ pub struct AlanWindowContext {
     buffer_width: Option<u32>,
     mouse_x: Option<u32>,
     mouse_y: Option<u32>,
+    mouse_down: HashSet<u32>,
+    mouse_clicked: HashSet<u32>,
+    scroll: f32,
     cursor_visible: bool,
     transparent: bool,
+    initial_size: Option<(u32, u32)>,
+    resizable: bool,
+    aux_buffers: u32,
+    surface_format: Option<String>,
 }
 
 impl AlanWindowContext {
+    fn new() -> AlanWindowContext {
+        AlanWindowContext {
+            window: None,
+            start: None,
+            buffer_width: None,
+            mouse_x: None,
+            mouse_y: None,
+            mouse_down: HashSet::new(),
+            mouse_clicked: HashSet::new(),
+            scroll: 0.0,
+            cursor_visible: true,
+            transparent: false,
+            initial_size: None,
+            resizable: true,
+            aux_buffers: 0,
+            surface_format: None,
+        }
+    }
+
     pub fn width(&self) -> u32 {
-        match self.window.as_ref() {
-            Some(win) => win.inner_size().width.max(1),
-            None => 0,
+        match (self.window.as_ref(), self.initial_size) {
+            (Some(win), _) => win.inner_size().width.max(1),
+            (None, Some((width, _))) => width,
+            (None, None) => 0,
         }
     }
 
     pub fn height(&self) -> u32 {
-        match self.window.as_ref() {
-            Some(win) => win.inner_size().height.max(1),
-            None => 0,
+        match (self.window.as_ref(), self.initial_size) {
+            (Some(win), _) => win.inner_size().height.max(1),
+            (None, Some((_, height))) => height,
+            (None, None) => 0,
         }
     }
 
@@ -1235,6 +2749,47 @@ impl AlanWindowContext {
         }
     }
 
+    /// Buttons are numbered like the DOM `MouseEvent.button`: 0 is the primary (left) button, 1 is
+    /// the middle button, 2 is the secondary (right) button, 3 and 4 are back and forward, and any
+    /// other buttons are numbered from 5 up
+    pub fn press_mouse_button(&mut self, button: u32) {
+        if self.mouse_down.insert(button) {
+            self.mouse_clicked.insert(button);
+        }
+    }
+
+    pub fn release_mouse_button(&mut self, button: u32) {
+        self.mouse_down.remove(&button);
+    }
+
+    pub fn is_mouse_down(&self, button: u32) -> bool {
+        self.mouse_down.contains(&button)
+    }
+
+    /// Whether the button went down since the prior frame was drawn, so a click held across many
+    /// frames is only seen once
+    pub fn clicked_this_frame(&self, button: u32) -> bool {
+        self.mouse_clicked.contains(&button)
+    }
+
+    /// Scrolling is measured in pixels, with positive values scrolling down like the DOM
+    /// `WheelEvent.deltaY`. Scroll events between frames are added together
+    pub fn scroll(&mut self, delta: f32) {
+        self.scroll += delta;
+    }
+
+    /// The total scrolled since the prior frame was drawn. It is reset by `end_frame` once the frame
+    /// is drawn rather than when read, so it can be read any number of times within a frame
+    pub fn scroll_delta(&self) -> f32 {
+        self.scroll
+    }
+
+    /// Resets the per-frame input state, called after each frame's context is generated
+    pub fn end_frame(&mut self) {
+        self.mouse_clicked.clear();
+        self.scroll = 0.0;
+    }
+
     pub fn cursor_visible(&mut self) {
         self.cursor_visible = true;
     }
@@ -1250,11 +2805,131 @@ impl AlanWindowContext {
     pub fn opaque(&mut self) {
         self.transparent = true;
     }
+
+    /// The size, in pixels, to open the window with. Without it the platform picks the size
+    pub fn initial_size(&mut self, width: u32, height: u32) {
+        self.initial_size = Some((width, height));
+    }
+
+    pub fn resizable(&mut self) {
+        self.resizable = true;
+    }
+
+    pub fn fixed_size(&mut self) {
+        self.resizable = false;
+    }
+
+    /// Requests extra buffers the same size as the framebuffer, for shader chains that render in
+    /// multiple passes. They are resized along with the framebuffer
+    pub fn aux_buffers(&mut self, count: u32) {
+        self.aux_buffers = count;
+    }
+
+    /// Overrides the texture format the window surface is configured with. Formats are named like
+    /// in WebGPU, eg `bgra8unorm-srgb`, and must be one the surface supports
+    pub fn surface_format(&mut self, format: &str) {
+        self.surface_format = Some(format.to_string());
+    }
+
+    /// Picks the requested surface format out of the supported formats, or the default format if
+    /// none was requested
+    pub fn select_surface_format(
+        &self,
+        supported: &[wgpu::TextureFormat],
+        default: wgpu::TextureFormat,
+    ) -> Result<wgpu::TextureFormat, AlanError> {
+        let requested = match &self.surface_format {
+            Some(f) => f.to_lowercase().replace('-', ""),
+            None => return Ok(default),
+        };
+        match supported
+            .iter()
+            .find(|f| format!("{:?}", f).to_lowercase() == requested)
+        {
+            Some(f) => Ok(*f),
+            None => Err(format!(
+                "Surface format {} is not supported, the supported formats are {}",
+                self.surface_format.as_ref().unwrap(),
+                supported
+                    .iter()
+                    .map(|f| format!("{:?}", f))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )
+            .into()),
+        }
+    }
+
+    /// The attributes to create the window with, once the initial context function has run
+    pub fn window_attributes(&self) -> WindowAttributes {
+        let attrs = Window::default_attributes()
+            .with_transparent(self.transparent)
+            .with_resizable(self.resizable);
+        match self.initial_size {
+            Some((width, height)) => {
+                attrs.with_inner_size(winit::dpi::PhysicalSize::new(width, height))
+            }
+            None => attrs,
+        }
+    }
 }
 
 pub struct AlanWindowFrame {
     pub context: GBuffer,
     pub framebuffer: GBuffer,
+    pub aux: Vec<GBuffer>,
+}
+
+impl AlanWindowFrame {
+    pub fn aux_buffer(&self, i: i64) -> Result<GBuffer, AlanError> {
+        match usize::try_from(i).ok().and_then(|i| self.aux.get(i)) {
+            Some(b) => Ok(b.clone()),
+            None => Err(format!(
+                "Auxiliary buffer {} does not exist, only {} were requested",
+                i,
+                self.aux.len()
+            )
+            .into()),
+        }
+    }
+}
+
+/// Numbers the mouse button like `press_mouse_button` expects. winit numbers its other buttons
+/// from 0, so they are moved past the named ones to not be confused with them
+fn mouse_button_index(button: winit::event::MouseButton) -> u32 {
+    match button {
+        winit::event::MouseButton::Left => 0,
+        winit::event::MouseButton::Middle => 1,
+        winit::event::MouseButton::Right => 2,
+        winit::event::MouseButton::Back => 3,
+        winit::event::MouseButton::Forward => 4,
+        winit::event::MouseButton::Other(b) => 5 + b as u32,
+    }
+}
+
+/// The number of bytes in each row of the framebuffer. Copying a buffer into a texture requires
+/// the rows to be a multiple of 256 bytes, so they are padded out to that
+fn framebuffer_row_bytes(width: u32) -> u32 {
+    if (4 * width) % 256 == 0 {
+        4 * width
+    } else {
+        (4 * width) + (256 - ((4 * width) % 256))
+    }
+}
+
+/// Creates a buffer for the window to render into
+fn window_buffer(device: &wgpu::Device, size: u64) -> GBuffer {
+    GBuffer {
+        buffer: Rc::new(device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: storage_buffer_type(),
+            mapped_at_creation: false,
+        })),
+        id: format!("buffer_{}", format!("{}", Uuid::new_v4()).replace("-", "_")),
+        element_size: 4,
+        len: (size / 4) as i64,
+    }
 }
 
 pub struct AlanWindow<C, R>
@@ -1271,9 +2946,12 @@ where
     queue: Option<wgpu::Queue>,
     context_buffer: Option<GBuffer>,
     buffer: Option<GBuffer>,
+    aux_buffers: Option<Vec<GBuffer>>,
     context_fn: C,
     gpgpu_shader_fn: R,
     gpgpu_shaders: Option<Vec<GPGPU>>,
+    surface_format: Option<wgpu::TextureFormat>,
+    error: Option<AlanError>,
     inited: bool,
 }
 
@@ -1282,7 +2960,7 @@ where
     C: FnMut(&mut AlanWindowContext) -> Vec<u32>,
     R: Fn(&AlanWindowFrame) -> Vec<GPGPU>,
 {
-    fn window_gpu_init(&mut self) {
+    fn window_gpu_init(&mut self) -> Result<(), AlanError> {
         if self.context.start.is_none() {
             self.context.start = Some(std::time::Instant::now());
         }
@@ -1325,6 +3003,19 @@ where
             self.device = Some(device);
             self.queue = Some(queue);
         }
+        if self.surface_format.is_none() {
+            let surface = self.surface.as_ref().unwrap();
+            let adapter = self.adapter.as_ref().unwrap();
+            let size = self.context.window.as_ref().unwrap().inner_size();
+            let default = surface
+                .get_default_config(adapter, size.width.max(1), size.height.max(1))
+                .unwrap()
+                .format;
+            self.surface_format = Some(
+                self.context
+                    .select_surface_format(&surface.get_capabilities(adapter).formats, default)?,
+            );
+        }
         if self.context_buffer.is_none() {
             let device = self.device.as_ref().unwrap();
             self.context_buffer = Some(GBuffer {
@@ -1336,6 +3027,7 @@ where
                 })),
                 id: format!("buffer_{}", format!("{}", Uuid::new_v4()).replace("-", "_")),
                 element_size: 4,
+                len: 4,
             });
         }
         if self.buffer.is_none() {
@@ -1343,23 +3035,15 @@ where
             let mut size = self.context.window.as_ref().unwrap().inner_size();
             size.width = size.width.max(1);
             size.height = size.height.max(1);
-            self.context.buffer_width = Some(if (4 * size.width) % 256 == 0 {
-                4 * size.width
-            } else {
-                (4 * size.width) + (256 - ((4 * size.width) % 256))
-            });
+            self.context.buffer_width = Some(framebuffer_row_bytes(size.width));
             let buffer_height = size.height;
             let buffer_size = (self.context.buffer_width.unwrap() as u64) * (buffer_height as u64);
-            self.buffer = Some(GBuffer {
-                buffer: Rc::new(device.create_buffer(&wgpu::BufferDescriptor {
-                    label: None,
-                    size: buffer_size,
-                    usage: storage_buffer_type(),
-                    mapped_at_creation: false,
-                })),
-                id: format!("buffer_{}", format!("{}", Uuid::new_v4()).replace("-", "_")),
-                element_size: 4,
-            });
+            self.buffer = Some(window_buffer(device, buffer_size));
+            self.aux_buffers = Some(
+                (0..self.context.aux_buffers)
+                    .map(|_| window_buffer(device, buffer_size))
+                    .collect(),
+            );
         }
         if self.gpgpu_shaders.is_none() {
             let mut size = self.context.window.as_ref().unwrap().inner_size();
@@ -1368,9 +3052,11 @@ where
             self.gpgpu_shaders = Some((self.gpgpu_shader_fn)(&AlanWindowFrame {
                 context: self.context_buffer.as_ref().unwrap().clone(),
                 framebuffer: self.buffer.as_ref().unwrap().clone(),
+                aux: self.aux_buffers.as_ref().unwrap().clone(),
             }));
         }
         self.inited = true;
+        Ok(())
     }
 }
 
@@ -1398,6 +3084,10 @@ where
                     b.destroy();
                 }
                 self.buffer = None;
+                for b in self.aux_buffers.iter().flatten() {
+                    b.destroy();
+                }
+                self.aux_buffers = None;
                 self.context_buffer = None;
                 self.queue = None;
                 self.device = None;
@@ -1410,50 +3100,44 @@ where
                     return;
                 }
                 if !self.inited {
-                    self.window_gpu_init();
+                    if let Err(e) = self.window_gpu_init() {
+                        self.error = Some(e);
+                        event_loop.exit();
+                        return;
+                    }
                 }
-                // We need to create a new buffer with the right size *and* replace all instances
-                // of the old buffer in the GPGPU array with the new one.
+                // We need to create new buffers with the right size *and* replace all instances
+                // of the old buffers in the GPGPU array with the new ones.
                 let device = self.device.as_ref().unwrap();
                 new_size.width = new_size.width.max(1);
                 new_size.height = new_size.height.max(1);
-                self.context.buffer_width = Some(if (4 * new_size.width) % 256 == 0 {
-                    4 * new_size.width
-                } else {
-                    (4 * new_size.width) + (256 - ((4 * new_size.width) % 256))
-                });
+                self.context.buffer_width = Some(framebuffer_row_bytes(new_size.width));
                 let buffer_height = new_size.height;
                 let buffer_size =
                     (self.context.buffer_width.unwrap() as u64) * (buffer_height as u64);
-                let old_buffer_id = self.buffer.as_ref().unwrap().id.clone();
-                let new_buffer = GBuffer {
-                    buffer: Rc::new(device.create_buffer(&wgpu::BufferDescriptor {
-                        label: None,
-                        size: buffer_size,
-                        usage: storage_buffer_type(),
-                        mapped_at_creation: false,
-                    })),
-                    id: format!("buffer_{}", format!("{}", Uuid::new_v4()).replace("-", "_")),
-                    element_size: 4,
-                };
+                // The auxiliary buffers are always the same size as the framebuffer
+                let old_buffers = std::iter::once(self.buffer.as_ref().unwrap())
+                    .chain(self.aux_buffers.as_ref().unwrap().iter())
+                    .collect::<Vec<&GBuffer>>();
+                let new_buffers = old_buffers
+                    .iter()
+                    .map(|_| window_buffer(device, buffer_size))
+                    .collect::<Vec<GBuffer>>();
                 for shader in self.gpgpu_shaders.as_mut().unwrap() {
                     for group in &mut shader.buffers {
-                        let mut idx = None;
-                        for (i, buffer) in group.iter().enumerate() {
-                            if buffer.id == old_buffer_id {
-                                idx = Some(i);
-                                break;
+                        for buffer in group.iter_mut() {
+                            if let Some(i) = old_buffers.iter().position(|b| b.id == buffer.id) {
+                                *buffer = new_buffers[i].clone();
                             }
                         }
-                        if let Some(id) = idx {
-                            group[id] = new_buffer.clone();
-                        }
                     }
                 }
-                if let Some(b) = &self.buffer {
+                for b in old_buffers {
                     b.destroy();
                 }
-                self.buffer = Some(new_buffer);
+                let mut new_buffers = new_buffers.into_iter();
+                self.buffer = new_buffers.next();
+                self.aux_buffers = Some(new_buffers.collect());
                 self.context.window.as_ref().unwrap().request_redraw();
             }
             WindowEvent::RedrawRequested => {
@@ -1462,7 +3146,11 @@ where
                 }
                 let frame_start = std::time::Instant::now();
                 if !self.inited {
-                    self.window_gpu_init();
+                    if let Err(e) = self.window_gpu_init() {
+                        self.error = Some(e);
+                        event_loop.exit();
+                        return;
+                    }
                 }
                 let window = self.context.window.as_ref().unwrap();
                 // TODO: These shouldn't be set every frame
@@ -1479,6 +3167,7 @@ where
                 let mut config = surface
                     .get_default_config(adapter, size.width, size.height)
                     .unwrap();
+                config.format = self.surface_format.unwrap();
                 config.usage =
                     wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::RENDER_ATTACHMENT;
                 config.present_mode = wgpu::PresentMode::Fifo;
@@ -1493,6 +3182,7 @@ where
                 let mut encoder =
                     device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
                 let context_array = (self.context_fn)(&mut self.context);
+                self.context.end_frame();
                 let context_slice = &context_array[..];
                 let context_ptr = context_slice.as_ptr();
                 let context_u8_len = context_array.len() * 4;
@@ -1509,21 +3199,23 @@ where
                     )),
                     id: old_context_buffer_id.clone(),
                     element_size: 4,
+                    len: context_array.len() as i64,
                 };
                 let ggs = self.gpgpu_shaders.as_mut().unwrap();
                 for gg in ggs {
                     if gg.module.is_none() {
-                        gg.module =
-                            Some(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                        gg.module = Some(Rc::new(device.create_shader_module(
+                            wgpu::ShaderModuleDescriptor {
                                 label: None,
                                 source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(
                                     &gg.source,
                                 )),
-                            }));
+                            },
+                        )));
                     }
                     let module = gg.module.as_ref().unwrap();
                     if gg.compute_pipeline.is_none() {
-                        gg.compute_pipeline = Some(device.create_compute_pipeline(
+                        gg.compute_pipeline = Some(Rc::new(device.create_compute_pipeline(
                             &wgpu::ComputePipelineDescriptor {
                                 label: None,
                                 layout: None,
@@ -1532,7 +3224,7 @@ where
                                 compilation_options: wgpu::PipelineCompilationOptions::default(),
                                 cache: None,
                             },
-                        ));
+                        )));
                     }
                     let compute_pipeline = gg.compute_pipeline.as_ref().unwrap();
                     let mut bind_groups = Vec::new();
@@ -1622,6 +3314,23 @@ where
                     self.context.mouse_y = Some(position.y as u32);
                 }
             }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = mouse_button_index(button);
+                match state {
+                    winit::event::ElementState::Pressed => self.context.press_mouse_button(button),
+                    winit::event::ElementState::Released => {
+                        self.context.release_mouse_button(button)
+                    }
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                // winit uses positive values for scrolling up, so flip them to match the DOM, and
+                // treat each line as 16 pixels
+                self.context.scroll(match delta {
+                    winit::event::MouseScrollDelta::LineDelta(_, y) => -y * 16.0,
+                    winit::event::MouseScrollDelta::PixelDelta(p) => -p.y as f32,
+                });
+            }
             _ => {} // Ignore all other events
         }
     }
@@ -1636,17 +3345,9 @@ where
     C: FnMut(&mut AlanWindowContext) -> Vec<u32>,
     R: Fn(&AlanWindowFrame) -> Vec<GPGPU>,
 {
-    let mut context = AlanWindowContext {
-        window: None,
-        start: None,
-        buffer_width: None,
-        mouse_x: None,
-        mouse_y: None,
-        cursor_visible: true,
-        transparent: false,
-    };
+    let mut context = AlanWindowContext::new();
     initial_context_fn(&mut context);
-    let config = Window::default_attributes().with_transparent(context.transparent);
+    let config = context.window_attributes();
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll); // TODO: This should also be configurable
     let mut app = AlanWindow {
@@ -1659,15 +3360,227 @@ where
         queue: None,
         context_buffer: None,
         buffer: None,
+        aux_buffers: None,
         context_fn,
         gpgpu_shader_fn,
         gpgpu_shaders: None,
+        surface_format: None,
+        error: None,
         inited: false,
     };
     match event_loop.run_app(&mut app) {
-        Ok(_) => Ok(()),
+        Ok(_) => match app.error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        },
         Err(e) => Err(AlanError {
             message: format!("{:?}", e),
         }),
     }
 }
+
+/// `render_frame` renders a single frame of a window program on the default GPU without opening a
+/// window, and saves it to a PNG file with `save_frame`. There is no window to take the size from,
+/// so the initial context function must set the `initial_size` to render at
+pub fn render_frame<C, R>(
+    mut initial_context_fn: impl FnMut(&mut AlanWindowContext),
+    mut context_fn: C,
+    gpgpu_shader_fn: R,
+    path: &str,
+) -> Result<(), AlanError>
+where
+    C: FnMut(&mut AlanWindowContext) -> Vec<u32>,
+    R: Fn(&AlanWindowFrame) -> Vec<GPGPU>,
+{
+    let mut context = AlanWindowContext::new();
+    initial_context_fn(&mut context);
+    let (width, height) = match context.initial_size {
+        Some((width, height)) => (width.max(1), height.max(1)),
+        None => return Err("Rendering without a window requires an initial size".into()),
+    };
+    context.start = Some(std::time::Instant::now());
+    context.buffer_width = Some(framebuffer_row_bytes(width));
+    let buffer_size = (context.buffer_width.unwrap() as u64) * (height as u64);
+    let g = gpu();
+    let frame = AlanWindowFrame {
+        context: create_buffer_init(&storage_buffer_type(), &context_fn(&mut context), &4),
+        framebuffer: window_buffer(&g.device, buffer_size),
+        aux: (0..context.aux_buffers)
+            .map(|_| window_buffer(&g.device, buffer_size))
+            .collect(),
+    };
+    let mut shaders = gpgpu_shader_fn(&frame);
+    // Resolve the magic workgroup sizes for the frame width and height, like the window does
+    for shader in &mut shaders {
+        for size in &mut shader.workgroup_sizes {
+            *size = match *size {
+                -1 => width as i64,
+                -2 => height as i64,
+                otherwise => otherwise,
+            };
+        }
+    }
+    gpu_run_list(&mut shaders);
+    save_frame(&frame.framebuffer, width, height, path)
+}
+
+/// `save_frame` writes a framebuffer on the default GPU to a PNG file. Each pixel is read as RGBA8,
+/// and the padding at the end of each row is dropped
+pub fn save_frame(
+    framebuffer: &GBuffer,
+    width: u32,
+    height: u32,
+    path: &str,
+) -> Result<(), AlanError> {
+    let row_bytes = framebuffer_row_bytes(width) as usize;
+    let bytes = read_buffer::<u32>(framebuffer)
+        .into_iter()
+        .flat_map(|p| p.to_le_bytes())
+        .collect::<Vec<u8>>();
+    if bytes.len() < row_bytes * (height as usize) {
+        return Err(format!(
+            "The framebuffer is too small for a {}x{} frame",
+            width, height
+        )
+        .into());
+    }
+    let pixels = bytes
+        .chunks_exact(row_bytes)
+        .take(height as usize)
+        .flat_map(|row| row[..4 * (width as usize)].iter().copied())
+        .collect::<Vec<u8>>();
+    image::save_buffer(
+        path,
+        &pixels,
+        width,
+        height,
+        image::ExtendedColorType::Rgba8,
+    )
+    .map_err(|e| format!("Could not save the frame to {}: {}", path, e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mouse_buttons() {
+        let mut ctx = AlanWindowContext::new();
+        assert!(!ctx.is_mouse_down(0));
+        ctx.press_mouse_button(0);
+        assert!(ctx.is_mouse_down(0));
+        assert!(ctx.clicked_this_frame(0));
+        assert!(!ctx.is_mouse_down(2));
+        // The click is only seen until the frame ends, but the button stays down until released
+        ctx.end_frame();
+        assert!(ctx.is_mouse_down(0));
+        assert!(!ctx.clicked_this_frame(0));
+        // Holding the button down is not another click
+        ctx.press_mouse_button(0);
+        assert!(!ctx.clicked_this_frame(0));
+        ctx.release_mouse_button(0);
+        assert!(!ctx.is_mouse_down(0));
+        // A click released within the same frame is still seen
+        ctx.press_mouse_button(1);
+        ctx.release_mouse_button(1);
+        assert!(!ctx.is_mouse_down(1));
+        assert!(ctx.clicked_this_frame(1));
+        ctx.end_frame();
+        assert!(!ctx.clicked_this_frame(1));
+    }
+
+    #[test]
+    fn scroll_delta() {
+        let mut ctx = AlanWindowContext::new();
+        assert_eq!(ctx.scroll_delta(), 0.0);
+        ctx.scroll(16.0);
+        ctx.scroll(-4.5);
+        assert_eq!(ctx.scroll_delta(), 11.5);
+        // Reading it doesn't reset it, ending the frame does
+        assert_eq!(ctx.scroll_delta(), 11.5);
+        ctx.end_frame();
+        assert_eq!(ctx.scroll_delta(), 0.0);
+        ctx.scroll(2.0);
+        assert_eq!(ctx.scroll_delta(), 2.0);
+    }
+
+    #[test]
+    fn other_mouse_buttons() {
+        use winit::event::MouseButton;
+        assert_eq!(mouse_button_index(MouseButton::Back), 3);
+        assert_eq!(mouse_button_index(MouseButton::Forward), 4);
+        // Other buttons don't collide with back and forward
+        for b in 0..5 {
+            assert!(mouse_button_index(MouseButton::Other(b)) > 4);
+        }
+        assert_ne!(
+            mouse_button_index(MouseButton::Other(0)),
+            mouse_button_index(MouseButton::Other(1))
+        );
+    }
+
+    #[test]
+    fn select_surface_format() {
+        use wgpu::TextureFormat;
+        let supported = [TextureFormat::Bgra8UnormSrgb, TextureFormat::Rgba8Unorm];
+        let mut ctx = AlanWindowContext::new();
+        // Without a requested format the default is used
+        assert_eq!(
+            ctx.select_surface_format(&supported, TextureFormat::Bgra8UnormSrgb)
+                .unwrap(),
+            TextureFormat::Bgra8UnormSrgb
+        );
+        // The WebGPU name is matched regardless of case and dashes
+        for name in ["rgba8unorm", "RGBA8Unorm"] {
+            ctx.surface_format(name);
+            assert_eq!(
+                ctx.select_surface_format(&supported, TextureFormat::Bgra8UnormSrgb)
+                    .unwrap(),
+                TextureFormat::Rgba8Unorm
+            );
+        }
+        for name in ["bgra8unorm-srgb", "bgra8unormsrgb", "BGRA8UNORM-SRGB"] {
+            ctx.surface_format(name);
+            assert_eq!(
+                ctx.select_surface_format(&supported, TextureFormat::Rgba8Unorm)
+                    .unwrap(),
+                TextureFormat::Bgra8UnormSrgb
+            );
+        }
+        // An unsupported format is an error listing the supported ones, not the default
+        ctx.surface_format("rgba16float");
+        let err = ctx
+            .select_surface_format(&supported, TextureFormat::Bgra8UnormSrgb)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error: Surface format rgba16float is not supported, the supported formats are Bgra8UnormSrgb, Rgba8Unorm"
+        );
+    }
+
+    #[test]
+    fn window_attributes() {
+        let mut ctx = AlanWindowContext::new();
+        let attrs = ctx.window_attributes();
+        assert_eq!(attrs.title, Window::default_attributes().title);
+        assert_eq!(attrs.inner_size, None);
+        assert!(attrs.resizable);
+        assert!(!attrs.transparent);
+        ctx.initial_size(640, 480);
+        ctx.fixed_size();
+        ctx.transparent();
+        let attrs = ctx.window_attributes();
+        // The title is left as the default, it is replaced with the render time once drawing
+        assert_eq!(attrs.title, Window::default_attributes().title);
+        assert_eq!(
+            attrs.inner_size,
+            Some(winit::dpi::Size::Physical(winit::dpi::PhysicalSize::new(
+                640, 480
+            )))
+        );
+        assert!(!attrs.resizable);
+        assert!(attrs.transparent);
+        ctx.resizable();
+        assert!(ctx.window_attributes().resizable);
+    }
+}
@@ -10,7 +10,7 @@ pub fn compile(src: &str) -> String {
     if let Err(e) = Scope::from_src("program.ln", src.to_string()) {
         return format!("{:?}", e);
     }
-    match lntojs("program.ln".to_string()) {
+    match lntojs("program.ln".to_string(), "main".to_string()) {
         Err(e) => format!("{:?}", e),
         Ok((js, _)) => js,
     }